@@ -0,0 +1,253 @@
+use crate::{Ideograph, Point, StrokeProcessed, encoded_points};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Largest horizontal shear (x offset per unit of y) the correction may apply
+const MAX_SLANT: f64 = 0.5;
+
+/// Allowed range for the learned width/height distortion
+const MIN_ASPECT: f64 = 0.5;
+const MAX_ASPECT: f64 = 2.0;
+
+/// Slowest learning rate, reached once enough samples have been collected
+const MIN_LEARNING_RATE: f64 = 0.1;
+
+/// Systematic distortion of a user's handwriting relative to the reference glyphs
+/// Modelled as x_drawn = aspect * x_reference + slant * y_drawn
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Distortion {
+    pub slant: f64,
+    pub aspect: f64,
+}
+
+impl Default for Distortion {
+    fn default() -> Self {
+        Self {
+            slant: 0.0,
+            aspect: 1.0,
+        }
+    }
+}
+
+impl Distortion {
+    /// True when the correction leaves points unchanged
+    pub fn is_identity(&self) -> bool {
+        self.slant == 0.0 && self.aspect == 1.0
+    }
+
+    /// Apply the inverse transform, mapping a drawn point towards reference proportions
+    pub fn apply(&self, point: Point) -> Point {
+        [(point[0] - self.slant * point[1]) / self.aspect, point[1]]
+    }
+}
+
+/// Per-user adaptation state learned from candidate selections
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserAdaptation {
+    /// How often each character was selected
+    pub selections: BTreeMap<Ideograph, u32>,
+    /// Selections that overrode the matcher: top candidate -> chosen character -> count
+    pub confusions: BTreeMap<Ideograph, BTreeMap<Ideograph, u32>>,
+    /// Learned handwriting distortion, undone during preprocessing
+    pub distortion: Distortion,
+    /// Number of selections that contributed to the distortion estimate
+    pub distortion_samples: u32,
+}
+
+impl UserAdaptation {
+    /// Load adaptation state from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Save adaptation state as JSON
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Forget everything learned so far
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// True when no selection has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.selections.is_empty() && self.distortion_samples == 0
+    }
+
+    /// Ranking boost for a character the user writes often
+    #[inline]
+    pub fn boost(&self, character: &str, weight: f64) -> f64 {
+        match self.selections.get(character) {
+            Some(&count) => weight * (1.0 + count as f64).ln(),
+            None => 0.0,
+        }
+    }
+
+    /// Characters the user picked instead of `top`, with their ranking boosts
+    pub fn confusion_boosts<'a>(
+        &'a self,
+        top: &str,
+        weight: f64,
+    ) -> impl Iterator<Item = (&'a str, f64)> + 'a {
+        self.confusions
+            .get(top)
+            .into_iter()
+            .flatten()
+            .map(move |(chosen, &count)| (chosen.as_str(), weight * (1.0 + count as f64).ln()))
+    }
+
+    /// Record that the user chose `selected` while the matcher ranked `top` first
+    /// `input` is the uncorrected preprocessed input, `reference` the chosen template
+    pub fn record(
+        &mut self,
+        selected: &str,
+        top: Option<&str>,
+        input: &[StrokeProcessed],
+        reference: Option<&[StrokeProcessed]>,
+    ) {
+        *self.selections.entry(selected.to_string()).or_default() += 1;
+
+        if let Some(top) = top
+            && top != selected
+        {
+            *self
+                .confusions
+                .entry(top.to_string())
+                .or_default()
+                .entry(selected.to_string())
+                .or_default() += 1;
+        }
+
+        if let Some(estimate) = reference.and_then(|r| estimate_distortion(input, r)) {
+            // Running mean for the first samples, then an exponential moving average
+            let rate = (1.0 / (self.distortion_samples + 1) as f64).max(MIN_LEARNING_RATE);
+            let d = &mut self.distortion;
+            d.slant = (d.slant + rate * (estimate.slant - d.slant)).clamp(-MAX_SLANT, MAX_SLANT);
+            d.aspect =
+                (d.aspect + rate * (estimate.aspect - d.aspect)).clamp(MIN_ASPECT, MAX_ASPECT);
+            self.distortion_samples += 1;
+        }
+    }
+}
+
+/// Collect the sampled points of a preprocessed stroke sequence
+fn sampled_points(strokes: &[StrokeProcessed]) -> Vec<Point> {
+    strokes
+        .iter()
+        .flat_map(|s| (0..encoded_points(s)).map(move |i| [s[2 * i], s[2 * i + 1]]))
+        .collect()
+}
+
+/// Fit the slant and aspect that map reference points onto input points
+/// Solves x_in = aspect * x_ref + slant * y_in by least squares on centered,
+/// y-scaled coordinates; returns None for degenerate (flat or mismatched) input
+fn estimate_distortion(
+    input: &[StrokeProcessed],
+    reference: &[StrokeProcessed],
+) -> Option<Distortion> {
+    if input.len() != reference.len() {
+        return None;
+    }
+
+    let standardize = |points: Vec<Point>| -> Option<Vec<Point>> {
+        let n = points.len() as f64;
+        let cx = points.iter().map(|p| p[0]).sum::<f64>() / n;
+        let cy = points.iter().map(|p| p[1]).sum::<f64>() / n;
+        let sy = (points.iter().map(|p| (p[1] - cy).powi(2)).sum::<f64>() / n).sqrt();
+        if sy < 1.0 {
+            return None;
+        }
+        Some(
+            points
+                .iter()
+                .map(|p| [(p[0] - cx) / sy, (p[1] - cy) / sy])
+                .collect(),
+        )
+    };
+
+    let drawn = standardize(sampled_points(input))?;
+    let expected = standardize(sampled_points(reference))?;
+
+    // Normal equations for the regressors (x_ref, y_in)
+    let (mut suu, mut suy, mut syy, mut sxu, mut sxy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (d, e) in drawn.iter().zip(&expected) {
+        suu += e[0] * e[0];
+        suy += e[0] * d[1];
+        syy += d[1] * d[1];
+        sxu += d[0] * e[0];
+        sxy += d[0] * d[1];
+    }
+
+    let det = suu * syy - suy * suy;
+    if det.abs() < 1e-9 {
+        return None;
+    }
+
+    let aspect = (sxu * syy - sxy * suy) / det;
+    let slant = (sxy * suu - sxu * suy) / det;
+    if !aspect.is_finite() || aspect <= 0.0 || !slant.is_finite() {
+        return None;
+    }
+
+    Some(Distortion { slant, aspect })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(points: &[[Point; 4]]) -> Vec<StrokeProcessed> {
+        points
+            .iter()
+            .map(|stroke| {
+                let mut s: StrokeProcessed = stroke.iter().flatten().copied().collect();
+                s.extend([0.0, 0.0]);
+                s
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate_distortion_recovers_slant() {
+        let reference = [
+            [[0.0, 0.0], [0.0, 80.0], [0.0, 160.0], [0.0, 255.0]],
+            [[0.0, 0.0], [80.0, 0.0], [160.0, 0.0], [255.0, 0.0]],
+            [[255.0, 0.0], [255.0, 80.0], [255.0, 160.0], [255.0, 255.0]],
+        ];
+        let slanted = reference.map(|s| s.map(|p| [p[0] + 0.25 * p[1], p[1]]));
+
+        let estimate = estimate_distortion(&encode(&slanted), &encode(&reference)).unwrap();
+        assert!((estimate.slant - 0.25).abs() < 1e-6);
+        assert!((estimate.aspect - 1.0).abs() < 1e-6);
+
+        // Undoing the estimate restores the reference shape
+        let restored = estimate.apply(slanted[2][3]);
+        assert!((restored[0] - 255.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_selection_boost_and_reset() {
+        let mut adaptation = UserAdaptation::default();
+        assert!(adaptation.is_empty());
+        assert_eq!(adaptation.boost("内", 8.0), 0.0);
+
+        adaptation.record("内", Some("內"), &[], None);
+        adaptation.record("内", Some("內"), &[], None);
+        assert!(adaptation.boost("内", 8.0) > 0.0);
+        assert_eq!(adaptation.confusion_boosts("內", 8.0).count(), 1);
+
+        // State survives a JSON round trip
+        let json = serde_json::to_string(&adaptation).unwrap();
+        let restored: UserAdaptation = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, adaptation);
+
+        adaptation.reset();
+        assert!(adaptation.is_empty());
+        assert!(adaptation.confusions.is_empty());
+    }
+}
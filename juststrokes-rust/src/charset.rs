@@ -0,0 +1,83 @@
+use crate::Ideograph;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Set of characters allowed as candidates
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CharacterFilter {
+    allowed: HashSet<Ideograph>,
+}
+
+impl CharacterFilter {
+    /// Build a filter from every non-whitespace character in `text`
+    pub fn from_chars(text: &str) -> Self {
+        Self {
+            allowed: text
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    /// True when the character may be returned
+    #[inline]
+    pub fn allows(&self, character: &str) -> bool {
+        self.allowed.contains(character)
+    }
+
+    /// Characters allowed by both filters
+    pub fn intersection(&self, other: &CharacterFilter) -> Self {
+        self.allowed.intersection(&other.allowed).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.allowed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allowed.is_empty()
+    }
+}
+
+impl FromIterator<Ideograph> for CharacterFilter {
+    fn from_iter<I: IntoIterator<Item = Ideograph>>(iter: I) -> Self {
+        Self {
+            allowed: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Load a character set file (e.g. an HSK level, GB2312 or Big5 coverage list)
+/// Format: UTF-8 text; every non-whitespace character is allowed, lines
+/// starting with # are comments
+pub fn load_charset<P: AsRef<Path>>(
+    path: P,
+) -> Result<CharacterFilter, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let text: String = content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    Ok(CharacterFilter::from_chars(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_charset() {
+        let path = std::env::temp_dir().join("juststrokes_charset_test.txt");
+        fs::write(&path, "# HSK 1\n爱 八 爸\n杯子\n").unwrap();
+
+        let filter = load_charset(&path).expect("Failed to load charset");
+        assert_eq!(filter.len(), 5);
+        assert!(filter.allows("杯"));
+        assert!(!filter.allows("H"));
+        assert!(!filter.allows("龘"));
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{Point, Stroke, get_aabb};
+use serde::{Deserialize, Serialize};
+
+/// Raw input cleanup applied before normalization, in the order
+/// dedup, dehook, smooth, simplify
+/// Distances are fractions of the input's bounding box diagonal, so the
+/// same settings work for any canvas resolution
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CleanupOptions {
+    /// Drop points closer than `dedup_distance` to the previous kept point
+    pub dedup: bool,
+    pub dedup_distance: f64,
+    /// Replace each inner point with a weighted average of its neighbours
+    pub smooth: bool,
+    /// Cut short hooks at pen-down and pen-up
+    pub dehook: bool,
+    /// Longest hook as a fraction of the stroke length
+    pub hook_length: f64,
+    /// Smallest turn in degrees that counts as a hook
+    pub hook_angle: f64,
+    /// Ramer-Douglas-Peucker simplification
+    pub simplify: bool,
+    pub simplify_epsilon: f64,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        Self {
+            dedup: false,
+            dedup_distance: 0.01,
+            smooth: false,
+            dehook: false,
+            hook_length: 0.1,
+            hook_angle: 90.0,
+            simplify: false,
+            simplify_epsilon: 0.01,
+        }
+    }
+}
+
+impl CleanupOptions {
+    /// True when at least one step is switched on
+    pub fn is_enabled(&self) -> bool {
+        self.dedup || self.smooth || self.dehook || self.simplify
+    }
+}
+
+/// Apply the enabled cleanup steps to every stroke
+/// Strokes are never emptied: each keeps at least its first point
+pub fn clean_strokes(strokes: &[Stroke], opts: &CleanupOptions) -> Vec<Stroke> {
+    let aabb = get_aabb(strokes);
+    let diagonal = distance(aabb[0], aabb[1]);
+
+    strokes
+        .iter()
+        .map(|stroke| {
+            let mut stroke = stroke.clone();
+            if opts.dedup {
+                stroke = dedup(&stroke, opts.dedup_distance * diagonal);
+            }
+            // Hooks are cut before smoothing would round them off
+            if opts.dehook {
+                stroke = dehook(&stroke, opts.hook_length, opts.hook_angle);
+            }
+            if opts.smooth {
+                stroke = smooth(&stroke);
+            }
+            if opts.simplify {
+                stroke = simplify(&stroke, opts.simplify_epsilon * diagonal);
+            }
+            stroke
+        })
+        .collect()
+}
+
+fn distance(p0: Point, p1: Point) -> f64 {
+    (p1[0] - p0[0]).hypot(p1[1] - p0[1])
+}
+
+/// Remove points within `min_distance` of the previous kept point
+/// The last point replaces a near-duplicate predecessor so the stroke keeps its end
+pub fn dedup(stroke: &Stroke, min_distance: f64) -> Stroke {
+    let mut result: Stroke = Vec::with_capacity(stroke.len());
+    for (i, &point) in stroke.iter().enumerate() {
+        let Some(&last) = result.last() else {
+            result.push(point);
+            continue;
+        };
+        if distance(last, point) > min_distance {
+            result.push(point);
+        } else if i == stroke.len() - 1 && result.len() > 1 {
+            let end = result.len() - 1;
+            result[end] = point;
+        }
+    }
+    result
+}
+
+/// Smooth with a [1, 2, 1] kernel, keeping both end points
+pub fn smooth(stroke: &Stroke) -> Stroke {
+    if stroke.len() < 3 {
+        return stroke.clone();
+    }
+    let mut result = stroke.clone();
+    for i in 1..stroke.len() - 1 {
+        let (a, b, c) = (stroke[i - 1], stroke[i], stroke[i + 1]);
+        result[i] = [
+            (a[0] + 2.0 * b[0] + c[0]) / 4.0,
+            (a[1] + 2.0 * b[1] + c[1]) / 4.0,
+        ];
+    }
+    result
+}
+
+/// Turn in degrees at `b` when going from `a` through `b` to `c` (0 = straight on)
+fn turn_angle(a: Point, b: Point, c: Point) -> f64 {
+    let u = [b[0] - a[0], b[1] - a[1]];
+    let v = [c[0] - b[0], c[1] - b[1]];
+    if (u[0] == 0.0 && u[1] == 0.0) || (v[0] == 0.0 && v[1] == 0.0) {
+        return 0.0;
+    }
+    let cross = u[0] * v[1] - u[1] * v[0];
+    let dot = u[0] * v[0] + u[1] * v[1];
+    cross.atan2(dot).abs().to_degrees()
+}
+
+/// Cut hooks at either end: the sharpest turn of at least `min_angle` degrees
+/// within `max_fraction` of the stroke length from that end
+pub fn dehook(stroke: &Stroke, max_fraction: f64, min_angle: f64) -> Stroke {
+    let n = stroke.len();
+    if n < 4 {
+        return stroke.clone();
+    }
+
+    let mut travelled = vec![0.0; n];
+    for i in 1..n {
+        travelled[i] = travelled[i - 1] + distance(stroke[i - 1], stroke[i]);
+    }
+    let limit = max_fraction * travelled[n - 1];
+
+    // Pen-down hook: the turn at k is measured towards the stroke end
+    let mut start = 0;
+    let mut sharpest = min_angle;
+    for k in 1..n - 1 {
+        if travelled[k] > limit {
+            break;
+        }
+        let angle = turn_angle(stroke[0], stroke[k], stroke[n - 1]);
+        if angle >= sharpest {
+            sharpest = angle;
+            start = k;
+        }
+    }
+
+    // Pen-up hook: the turn at k is measured from the (dehooked) stroke start
+    let mut end = n - 1;
+    let mut sharpest = min_angle;
+    for k in (start + 1..n - 1).rev() {
+        if travelled[n - 1] - travelled[k] > limit {
+            break;
+        }
+        let angle = turn_angle(stroke[start], stroke[k], stroke[n - 1]);
+        if angle >= sharpest {
+            sharpest = angle;
+            end = k;
+        }
+    }
+
+    stroke[start..=end].to_vec()
+}
+
+/// Ramer-Douglas-Peucker simplification with tolerance `epsilon`
+pub fn simplify(stroke: &Stroke, epsilon: f64) -> Stroke {
+    if stroke.len() < 3 {
+        return stroke.clone();
+    }
+    let mut keep = vec![false; stroke.len()];
+    keep[0] = true;
+    keep[stroke.len() - 1] = true;
+    simplify_range(stroke, 0, stroke.len() - 1, epsilon, &mut keep);
+
+    stroke
+        .iter()
+        .zip(keep)
+        .filter(|(_, kept)| *kept)
+        .map(|(&p, _)| p)
+        .collect()
+}
+
+fn simplify_range(stroke: &Stroke, first: usize, last: usize, epsilon: f64, keep: &mut [bool]) {
+    if last <= first + 1 {
+        return;
+    }
+    let (a, b) = (stroke[first], stroke[last]);
+    let length = distance(a, b);
+
+    let mut farthest = first;
+    let mut max_distance = 0.0;
+    for (i, &p) in stroke.iter().enumerate().take(last).skip(first + 1) {
+        let d = if length == 0.0 {
+            distance(a, p)
+        } else {
+            ((b[0] - a[0]) * (a[1] - p[1]) - (a[0] - p[0]) * (b[1] - a[1])).abs() / length
+        };
+        if d > max_distance {
+            max_distance = d;
+            farthest = i;
+        }
+    }
+
+    if max_distance > epsilon {
+        keep[farthest] = true;
+        simplify_range(stroke, first, farthest, epsilon, keep);
+        simplify_range(stroke, farthest, last, epsilon, keep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_and_simplify() {
+        let stroke = vec![
+            [0.0, 0.0],
+            [0.0, 0.0],
+            [0.2, 0.1],
+            [10.0, 0.3],
+            [20.0, -0.2],
+            [30.0, 0.0],
+            [30.1, 0.0],
+        ];
+        let deduped = dedup(&stroke, 0.5);
+        assert_eq!(
+            deduped,
+            vec![[0.0, 0.0], [10.0, 0.3], [20.0, -0.2], [30.1, 0.0]]
+        );
+
+        // Nearly straight: only the end points survive
+        assert_eq!(simplify(&deduped, 1.0), vec![[0.0, 0.0], [30.1, 0.0]]);
+
+        // A corner is kept
+        let corner = vec![[0.0, 0.0], [5.0, 0.1], [10.0, 0.0], [10.0, 10.0]];
+        assert_eq!(
+            simplify(&corner, 1.0),
+            vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]
+        );
+
+        // Taps never disappear
+        assert_eq!(dedup(&vec![[1.0, 1.0], [1.2, 1.0]], 0.5), vec![[1.0, 1.0]]);
+    }
+
+    #[test]
+    fn test_dehook() {
+        // Horizontal stroke with a small upward flick at pen-up and a
+        // backwards tick at pen-down
+        let stroke = vec![
+            [3.0, 1.0],
+            [0.0, 0.0],
+            [25.0, 0.0],
+            [50.0, 0.0],
+            [75.0, 0.0],
+            [100.0, 0.0],
+            [97.0, -4.0],
+        ];
+        let cleaned = dehook(&stroke, 0.1, 90.0);
+        assert_eq!(cleaned.first(), Some(&[0.0, 0.0]));
+        assert_eq!(cleaned.last(), Some(&[100.0, 0.0]));
+
+        // A straight stroke is untouched
+        let straight: Stroke = (0..5).map(|i| [i as f64 * 10.0, 0.0]).collect();
+        assert_eq!(dehook(&straight, 0.1, 90.0), straight);
+
+        let opts = CleanupOptions {
+            dedup: true,
+            smooth: true,
+            dehook: true,
+            simplify: true,
+            ..Default::default()
+        };
+        let cleaned = clean_strokes(&[stroke], &opts);
+        assert_eq!(cleaned[0].first(), Some(&[0.0, 0.0]));
+        assert_eq!(cleaned[0].len(), 2);
+    }
+}
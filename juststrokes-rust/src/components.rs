@@ -0,0 +1,317 @@
+use crate::adaptation::Distortion;
+use crate::grader::sampled_points;
+use crate::{Ideograph, Matcher, ScoredCandidate, Stroke, StrokeProcessed, preprocess_strokes};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Placeholder for an unknown component in makemeahanzi decompositions
+const UNKNOWN_COMPONENT: &str = "？";
+
+/// Parsed Ideographic Description Sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ids {
+    /// A component character
+    Leaf(String),
+    /// A description operator such as ⿰ with its parts
+    Compound(char, Vec<Ids>),
+}
+
+/// Number of parts taken by an IDS operator
+fn arity(c: char) -> Option<usize> {
+    match c {
+        '⿲' | '⿳' => Some(3),
+        '⿰'..='⿻' => Some(2),
+        _ => None,
+    }
+}
+
+/// Parse an IDS such as "⿰氵每"; None if it is malformed
+pub fn parse_ids(text: &str) -> Option<Ids> {
+    let mut chars = text.chars();
+    let ids = parse_node(&mut chars)?;
+    chars.next().is_none().then_some(ids)
+}
+
+fn parse_node(chars: &mut std::str::Chars<'_>) -> Option<Ids> {
+    let c = chars.next()?;
+    match arity(c) {
+        Some(n) => {
+            let parts = (0..n)
+                .map(|_| parse_node(chars))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Ids::Compound(c, parts))
+        }
+        None => Some(Ids::Leaf(c.to_string())),
+    }
+}
+
+impl Ids {
+    /// Known component characters with their paths, in reading order
+    pub fn leaves(&self) -> Vec<(Vec<usize>, &str)> {
+        let mut result = Vec::new();
+        self.collect_leaves(&mut Vec::new(), &mut result);
+        result
+    }
+
+    fn collect_leaves<'a>(
+        &'a self,
+        path: &mut Vec<usize>,
+        result: &mut Vec<(Vec<usize>, &'a str)>,
+    ) {
+        match self {
+            Ids::Leaf(c) if c != UNKNOWN_COMPONENT => result.push((path.clone(), c)),
+            Ids::Leaf(_) => {}
+            Ids::Compound(_, parts) => {
+                for (i, part) in parts.iter().enumerate() {
+                    path.push(i);
+                    part.collect_leaves(path, result);
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Options for building component templates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ComponentOptions {
+    /// Most templates kept per component and stroke count; characters listed
+    /// in the database come first, then instances cut out of other characters
+    pub max_templates: usize,
+}
+
+impl Default for ComponentOptions {
+    fn default() -> Self {
+        Self { max_templates: 8 }
+    }
+}
+
+/// Component recognized from handwriting with the characters containing it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentMatch {
+    pub component: ScoredCandidate,
+    /// Characters containing the component, most frequent first
+    pub characters: Vec<Ideograph>,
+}
+
+/// Recognizer for components (radicals and other parts) of characters
+/// Templates come from the database's own component characters and from
+/// stroke subsequences of decomposed characters (makemeahanzi's `matches`),
+/// re-normalized as if drawn alone
+pub struct ComponentRecognizer {
+    matcher: Matcher,
+    containing: HashMap<Ideograph, Vec<Ideograph>>,
+}
+
+impl ComponentRecognizer {
+    /// Build templates and the reverse index from a matcher's database and
+    /// metadata (see `Matcher::set_metadata`)
+    pub fn new(source: &Matcher, options: &ComponentOptions) -> Self {
+        let mut direct: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut decompositions: HashMap<&str, Ids> = HashMap::new();
+        for entry in source.metadata.iter() {
+            let Some(ids) = entry.decomposition.as_deref().and_then(parse_ids) else {
+                continue;
+            };
+            decompositions.insert(entry.character.as_str(), ids);
+        }
+        for (character, ids) in &decompositions {
+            let leaves = ids
+                .leaves()
+                .into_iter()
+                .map(|(_, c)| c)
+                .filter(|c| c != character)
+                .collect();
+            direct.insert(character, leaves);
+        }
+
+        let components: HashSet<&str> = direct.values().flatten().copied().collect();
+        let mut templates: Vec<(Ideograph, Vec<StrokeProcessed>)> = Vec::new();
+        let mut counts: HashMap<(Ideograph, usize), usize> = HashMap::new();
+        let mut add = |component: &str, strokes: Vec<StrokeProcessed>| {
+            let count = counts
+                .entry((component.to_string(), strokes.len()))
+                .or_default();
+            if *count < options.max_templates {
+                *count += 1;
+                templates.push((component.to_string(), strokes));
+            }
+        };
+
+        // Components written as characters of their own
+        for template in source.medians.iter().filter(|t| t.standard.is_none()) {
+            if let Some(&component) = components.get(template.character.as_str()) {
+                add(component, template.strokes.clone());
+            }
+        }
+
+        // Components cut out of the characters that contain them
+        for template in source.medians.iter().filter(|t| t.standard.is_none()) {
+            let (Some(ids), Some(entry)) = (
+                decompositions.get(template.character.as_str()),
+                source.metadata.get(&template.character),
+            ) else {
+                continue;
+            };
+            if entry.matches.len() != template.strokes.len() {
+                continue;
+            }
+            for (path, component) in ids.leaves() {
+                let raw: Vec<Stroke> = template
+                    .strokes
+                    .iter()
+                    .zip(&entry.matches)
+                    .filter(|(_, m)| m.as_ref().is_some_and(|m| m.starts_with(&path)))
+                    .map(|(s, _)| sampled_points(s))
+                    .collect();
+                if raw.is_empty() {
+                    continue;
+                }
+                let strokes = preprocess_strokes(
+                    &raw,
+                    source.layout,
+                    &source.params,
+                    &Distortion::default(),
+                    None,
+                );
+                add(component, strokes);
+            }
+        }
+
+        // Reverse index over nested decompositions (每 contains 母, so 海 does too)
+        let mut containing: HashMap<Ideograph, Vec<Ideograph>> = HashMap::new();
+        for &character in direct.keys() {
+            let mut seen = HashSet::new();
+            let mut pending = vec![character];
+            while let Some(current) = pending.pop() {
+                for &part in direct.get(current).into_iter().flatten() {
+                    if seen.insert(part) {
+                        pending.push(part);
+                    }
+                }
+            }
+            for part in seen {
+                containing
+                    .entry(part.to_string())
+                    .or_default()
+                    .push(character.to_string());
+            }
+        }
+        for characters in containing.values_mut() {
+            characters.sort_by(|a, b| {
+                source
+                    .frequency
+                    .count(b)
+                    .total_cmp(&source.frequency.count(a))
+                    .then_with(|| a.cmp(b))
+            });
+        }
+
+        Self {
+            matcher: Matcher::with_layout(templates, source.layout, Some(source.params))
+                .expect("component templates keep the source layout"),
+            containing,
+        }
+    }
+
+    /// Characters containing a component, most frequent first
+    pub fn characters_with(&self, component: &str) -> &[Ideograph] {
+        self.containing
+            .get(component)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Best-matching components for handwritten input
+    pub fn recognize(&self, strokes: &[Stroke], how_many: usize) -> Vec<ScoredCandidate> {
+        self.matcher.match_scored(strokes, how_many)
+    }
+
+    /// Best-matching components together with the characters containing them
+    pub fn lookup(&self, strokes: &[Stroke], how_many: usize) -> Vec<ComponentMatch> {
+        self.recognize(strokes, how_many)
+            .into_iter()
+            .map(|component| ComponentMatch {
+                characters: self.characters_with(&component.character).to_vec(),
+                component,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{RawDatabase, build_database};
+    use crate::metadata::{CharacterMetadata, MetadataStore};
+    use crate::{StrokeLayout, frequency::FrequencyTable};
+
+    #[test]
+    fn test_parse_ids() {
+        let ids = parse_ids("⿰氵⿱亠口").unwrap();
+        assert_eq!(
+            ids.leaves(),
+            vec![(vec![0], "氵"), (vec![1, 0], "亠"), (vec![1, 1], "口")]
+        );
+        assert_eq!(parse_ids("⿰氵"), None);
+        assert_eq!(parse_ids("⿱？一").unwrap().leaves(), vec![(vec![1], "一")]);
+    }
+
+    #[test]
+    fn test_component_lookup() {
+        let square = |x0: f64, y0: f64, x1: f64, y1: f64| -> Vec<Stroke> {
+            vec![
+                vec![[x0, y0], [x0, y1]],
+                vec![[x0, y0], [x1, y0], [x1, y1]],
+                vec![[x0, y1], [x1, y1]],
+            ]
+        };
+        let cross = |x0: f64, y0: f64, x1: f64, y1: f64| -> Vec<Stroke> {
+            let (cx, cy) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+            vec![vec![[x0, cy], [x1, cy]], vec![[cx, y0], [cx, y1]]]
+        };
+        // 口 itself is not in the database, only inside 叶 and 古
+        let ye = [
+            square(0.0, 30.0, 40.0, 70.0),
+            cross(50.0, 0.0, 100.0, 100.0),
+        ]
+        .concat();
+        let gu = [
+            cross(0.0, 0.0, 100.0, 50.0),
+            square(20.0, 55.0, 80.0, 100.0),
+        ]
+        .concat();
+        let raw: RawDatabase = vec![
+            ("叶".to_string(), ye),
+            ("古".to_string(), gu),
+            ("十".to_string(), cross(0.0, 0.0, 100.0, 100.0)),
+        ];
+        let mut matcher =
+            Matcher::new(build_database(&raw, StrokeLayout::default()).unwrap(), None);
+
+        let mut store = MetadataStore::default();
+        let entry = |character: &str, decomposition: &str, matches: &[usize]| CharacterMetadata {
+            character: character.to_string(),
+            decomposition: Some(decomposition.to_string()),
+            matches: matches.iter().map(|&m| Some(vec![m])).collect(),
+            ..Default::default()
+        };
+        store.insert(entry("叶", "⿰口十", &[0, 0, 0, 1, 1]));
+        store.insert(entry("古", "⿱十口", &[0, 0, 1, 1, 1]));
+        matcher.set_metadata(store);
+        matcher.set_frequency_table(FrequencyTable::from_counts([("古".to_string(), 5.0)]));
+
+        let recognizer = ComponentRecognizer::new(&matcher, &ComponentOptions::default());
+        assert_eq!(recognizer.characters_with("口"), ["古", "叶"]);
+        assert!(recognizer.characters_with("木").is_empty());
+
+        let drawn = square(10.0, 10.0, 60.0, 60.0);
+        let found = recognizer.lookup(&drawn, 1);
+        assert_eq!(found[0].component.character, "口");
+        assert_eq!(found[0].characters, ["古", "叶"]);
+        assert_eq!(
+            recognizer.recognize(&cross(0.0, 0.0, 10.0, 10.0), 1)[0].character,
+            "十"
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{Ideograph, StrokeProcessed};
+use crate::data::{check_stroke_layout, stroke_layout};
+use crate::{Ideograph, StrokeLayout, StrokeProcessed};
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
@@ -6,14 +7,35 @@ use std::path::Path;
 /// Database of characters with their preprocessed stroke features
 pub type CharacterDatabase = Vec<(Ideograph, Vec<StrokeProcessed>)>;
 
+/// Header line recording the number of sampled points per stroke
+const POINTS_HEADER: &str = "#points_per_stroke=";
+
+/// Header line recording whether strokes carry shape features
+const FEATURES_HEADER: &str = "#stroke_features=";
+
 /// Convert graphics.json to CSV format
-/// Format: character\tx0,y0,x1,y1,x2,y2,x3,y3,angle,length\t...
+/// Format: #points_per_stroke=N and #stroke_features=bool headers, then
+/// character\tx0,y0,...,angle,length\t...
 pub fn json_to_csv<P: AsRef<Path>, Q: AsRef<Path>>(
     json_path: P,
     csv_path: Q,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let data = crate::data::load_graphics_json(json_path)?;
-    let mut file = fs::File::create(csv_path)?;
+    save_graphics_csv(csv_path, &data)
+}
+
+/// Save a preprocessed database as CSV with the stroke layout headers
+pub fn save_graphics_csv<P: AsRef<Path>>(
+    path: P,
+    data: &CharacterDatabase,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = fs::File::create(path)?;
+
+    let layout = stroke_layout(data).unwrap_or_default();
+    writeln!(file, "{}{}", POINTS_HEADER, layout.points)?;
+    if layout.features {
+        writeln!(file, "{}true", FEATURES_HEADER)?;
+    }
 
     for (character, strokes) in data {
         write!(file, "{}", character)?;
@@ -33,17 +55,35 @@ pub fn json_to_csv<P: AsRef<Path>, Q: AsRef<Path>>(
 }
 
 /// Load preprocessed character database from CSV file
-/// Format: character\tx0,y0,x1,y1,x2,y2,x3,y3,angle,length\t...
-/// Tab-delimited, UTF-8 encoded
+/// Format: character\tx0,y0,...,angle,length\t...
+/// Tab-delimited, UTF-8 encoded; optional #points_per_stroke=N and
+/// #stroke_features=bool lines set the layout (4 points, no features without them)
 pub fn load_graphics_csv<P: AsRef<Path>>(
     path: P,
 ) -> Result<CharacterDatabase, Box<dyn std::error::Error>> {
+    load_graphics_csv_with_layout(path).map(|(database, _)| database)
+}
+
+/// Load a preprocessed CSV database with the stroke layout of its header
+/// lines, for `Matcher::with_layout`
+pub fn load_graphics_csv_with_layout<P: AsRef<Path>>(
+    path: P,
+) -> Result<(CharacterDatabase, StrokeLayout), Box<dyn std::error::Error>> {
     let file = fs::File::open(path)?;
     let reader = BufReader::new(file);
     let mut result = Vec::new();
+    let mut layout = StrokeLayout::default();
 
     for line in reader.lines() {
         let line = line?;
+        if let Some(value) = line.strip_prefix(POINTS_HEADER) {
+            layout.points = value.trim().parse()?;
+            continue;
+        }
+        if let Some(value) = line.strip_prefix(FEATURES_HEADER) {
+            layout.features = value.trim().parse()?;
+            continue;
+        }
         let mut parts = line.split('\t');
 
         // First column is the character
@@ -60,7 +100,8 @@ pub fn load_graphics_csv<P: AsRef<Path>>(
         result.push((character, strokes));
     }
 
-    Ok(result)
+    check_stroke_layout(&result, layout)?;
+    Ok((result, layout))
 }
 
 #[cfg(test)]
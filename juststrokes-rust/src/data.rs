@@ -1,23 +1,58 @@
-use crate::{Ideograph, StrokeProcessed};
-use serde_json::Value;
+use crate::adaptation::Distortion;
+use crate::{Ideograph, MatcherOptions, Stroke, StrokeLayout, StrokeProcessed, preprocess_strokes};
+use serde_json::{Value, json};
 use std::fs;
+use std::io::{BufRead, BufReader};
 use std::path::Path;
 
 /// Database of characters with their preprocessed stroke features
 pub type CharacterDatabase = Vec<(Ideograph, Vec<StrokeProcessed>)>;
 
+/// Characters with their raw median points, before preprocessing
+pub type RawDatabase = Vec<(Ideograph, Vec<Stroke>)>;
+
 /// Load preprocessed character database from JSON file
-/// Expected format: [[character, [[x0,y0,x1,y1,x2,y2,x3,y3,angle,length], ...]], ...]
-/// Each character maps to a list of strokes, each stroke has 10 values
+/// Expected format: {"points_per_stroke": N, "stroke_features": bool,
+/// "characters": [[character, strokes], ...]} where each stroke is
+/// [x0, y0, ..., x(N-1), y(N-1), angle, length] followed by the shape features
+/// if "stroke_features" is true (it defaults to false)
+/// A bare [[character, strokes], ...] array is the classic 4-point layout
 pub fn load_graphics_json<P: AsRef<Path>>(
     path: P,
 ) -> Result<CharacterDatabase, Box<dyn std::error::Error>> {
+    load_graphics_json_with_layout(path).map(|(database, _)| database)
+}
+
+/// Load a preprocessed JSON database with the stroke layout of its header,
+/// for `Matcher::with_layout`
+pub fn load_graphics_json_with_layout<P: AsRef<Path>>(
+    path: P,
+) -> Result<(CharacterDatabase, StrokeLayout), Box<dyn std::error::Error>> {
     let content = fs::read_to_string(path)?;
     let data: Value = serde_json::from_str(&content)?;
 
+    let (layout, entries) = match data {
+        Value::Object(mut header) => {
+            let points = header
+                .get("points_per_stroke")
+                .and_then(Value::as_u64)
+                .ok_or("Missing points_per_stroke in database header")?
+                as usize;
+            let features = header
+                .get("stroke_features")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            (
+                StrokeLayout { points, features },
+                header.remove("characters").unwrap_or(Value::Null),
+            )
+        }
+        entries => (StrokeLayout::default(), entries),
+    };
+
     let mut result = Vec::new();
 
-    if let Value::Array(entries) = data {
+    if let Value::Array(entries) = entries {
         for entry in entries {
             if let Value::Array(pair) = entry
                 && pair.len() == 2
@@ -45,9 +80,142 @@ pub fn load_graphics_json<P: AsRef<Path>>(
         }
     }
 
+    check_stroke_layout(&result, layout)?;
+    Ok((result, layout))
+}
+
+/// Save a preprocessed database as JSON with a points_per_stroke header
+pub fn save_graphics_json<P: AsRef<Path>>(
+    path: P,
+    database: &CharacterDatabase,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let layout = stroke_layout(database).unwrap_or_default();
+    let document = json!({
+        "points_per_stroke": layout.points,
+        "stroke_features": layout.features,
+        "characters": database,
+    });
+    fs::write(path, serde_json::to_string(&document)?)?;
+    Ok(())
+}
+
+/// Stroke layout of a database (None if it has no strokes)
+pub fn stroke_layout(database: &CharacterDatabase) -> Option<StrokeLayout> {
+    database
+        .iter()
+        .flat_map(|(_, strokes)| strokes.first())
+        .next()
+        .map(StrokeLayout::of)
+}
+
+/// Sampled points per stroke of a database (None if it has no strokes)
+pub fn points_per_stroke(database: &CharacterDatabase) -> Option<usize> {
+    stroke_layout(database).map(|layout| layout.points)
+}
+
+/// Ensure every stroke has the given layout
+pub(crate) fn check_stroke_layout(
+    database: &CharacterDatabase,
+    layout: StrokeLayout,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (character, strokes) in database {
+        check_strokes(character, strokes, layout)?;
+    }
+    Ok(())
+}
+
+/// Ensure every stroke of one character has the given layout
+pub(crate) fn check_strokes(
+    character: &str,
+    strokes: &[StrokeProcessed],
+    layout: StrokeLayout,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(stroke) = strokes.iter().find(|s| s.len() != layout.stroke_len()) {
+        return Err(format!(
+            "Stroke of {} has {} values, expected {} for {} points per stroke{}",
+            character,
+            stroke.len(),
+            layout.stroke_len(),
+            layout.points,
+            if layout.features {
+                " with features"
+            } else {
+                ""
+            }
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Load raw stroke medians from makemeahanzi's graphics.txt
+/// Format: one JSON object per line with "character" and "medians" fields;
+/// medians are in a 1024-unit box with y pointing up from a baseline at 900
+pub fn load_medians<P: AsRef<Path>>(path: P) -> Result<RawDatabase, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    let reader = BufReader::new(file);
+    let mut result = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Value = serde_json::from_str(&line)?;
+        let character = entry["character"]
+            .as_str()
+            .ok_or("Missing character field")?
+            .to_string();
+        let medians = entry["medians"].as_array().ok_or("Missing medians field")?;
+
+        let mut strokes = Vec::new();
+        for median in medians {
+            let stroke: Stroke = median
+                .as_array()
+                .ok_or("Invalid median")?
+                .iter()
+                .map(|point| match (point[0].as_f64(), point[1].as_f64()) {
+                    // Flip into screen coordinates (y pointing down)
+                    (Some(x), Some(y)) => Ok([x, 900.0 - y]),
+                    _ => Err("Invalid median point"),
+                })
+                .collect::<Result<_, _>>()?;
+            if stroke.is_empty() {
+                return Err(format!("Empty median in {}", character).into());
+            }
+            strokes.push(stroke);
+        }
+
+        result.push((character, strokes));
+    }
+
     Ok(result)
 }
 
+/// Preprocess raw medians into a database with the given stroke layout
+/// Characters without strokes are skipped; a layout with fewer than 2 points
+/// per stroke or an empty stroke is an error
+pub fn build_database(
+    raw: &RawDatabase,
+    layout: StrokeLayout,
+) -> Result<CharacterDatabase, Box<dyn std::error::Error>> {
+    if layout.points < 2 {
+        return Err("At least 2 points per stroke are needed".into());
+    }
+    let options = MatcherOptions::default();
+    raw.iter()
+        .filter(|(_, strokes)| !strokes.is_empty())
+        .map(|(character, strokes)| {
+            if strokes.iter().any(|s| s.is_empty()) {
+                return Err(format!("Empty stroke in {}", character).into());
+            }
+            let processed =
+                preprocess_strokes(strokes, layout, &options, &Distortion::default(), None);
+            Ok((character.clone(), processed))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,4 +237,117 @@ mod tests {
             assert_eq!(stroke.len(), 10);
         }
     }
+
+    #[test]
+    fn test_build_database_from_medians() {
+        let dir = std::env::temp_dir();
+        let medians_path = dir.join("juststrokes_medians_test.txt");
+        let db_path = dir.join("juststrokes_db8_test.json");
+        fs::write(
+            &medians_path,
+            concat!(
+                r#"{"character":"十","strokes":[],"medians":[[[100,400],[900,400]],[[500,800],[500,0]]]}"#,
+                "\n",
+                r#"{"character":"一","strokes":[],"medians":[[[100,400],[500,410],[900,400]]]}"#,
+                "\n"
+            ),
+        )
+        .unwrap();
+
+        let raw = load_medians(&medians_path).expect("Failed to load medians");
+        assert_eq!(raw[0].1[1][0], [500.0, 100.0]);
+
+        let layout = StrokeLayout {
+            points: 8,
+            features: false,
+        };
+        let database = build_database(&raw, layout).unwrap();
+        assert_eq!(points_per_stroke(&database), Some(8));
+        assert_eq!(database[0].1[0].len(), 18);
+        let single = StrokeLayout {
+            points: 1,
+            features: false,
+        };
+        assert!(build_database(&raw, single).is_err());
+
+        save_graphics_json(&db_path, &database).unwrap();
+        let (loaded, loaded_layout) =
+            load_graphics_json_with_layout(&db_path).expect("Failed to load database");
+        assert_eq!(loaded, database);
+        assert_eq!(loaded_layout, layout);
+
+        // Templates must all have the matcher's layout
+        let mut mixed = loaded.clone();
+        mixed.push(("一".to_string(), vec![vec![128.0; 10]]));
+        assert!(crate::Matcher::with_layout(mixed, layout, None).is_err());
+        let empty = crate::Matcher::with_layout(Vec::new(), layout, None).unwrap();
+        assert_eq!(empty.points_per_stroke(), 8);
+
+        // Input is sampled to match the database
+        let matcher = crate::Matcher::with_layout(loaded, loaded_layout, None).unwrap();
+        assert_eq!(matcher.points_per_stroke(), 8);
+        let strokes = vec![
+            vec![[0.0, 50.0], [100.0, 50.0]],
+            vec![[50.0, 0.0], [50.0, 100.0]],
+        ];
+        assert_eq!(matcher.match_strokes(&strokes, 1), vec!["十"]);
+
+        // A stroke of the wrong length is rejected
+        fs::write(
+            &db_path,
+            r#"{"points_per_stroke":8,"characters":[["一",[[1,2,3,4,5,6,7,8,9,10]]]]}"#,
+        )
+        .unwrap();
+        assert!(load_graphics_json(&db_path).is_err());
+
+        let _ = fs::remove_file(&medians_path);
+        let _ = fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_feature_database() {
+        // ㇕ with a sharp corner and a smooth arc between the same end points
+        let arc: Stroke = (0..=16)
+            .map(|i| {
+                let t = i as f64 / 16.0 * std::f64::consts::FRAC_PI_2;
+                [100.0 + 800.0 * t.sin(), 100.0 + 800.0 * (1.0 - t.cos())]
+            })
+            .collect();
+        let raw: RawDatabase = vec![
+            (
+                "㇕".to_string(),
+                vec![vec![[100.0, 100.0], [900.0, 100.0], [900.0, 900.0]]],
+            ),
+            ("㇁".to_string(), vec![arc]),
+        ];
+        let layout = StrokeLayout {
+            points: 4,
+            features: true,
+        };
+        let database = build_database(&raw, layout).unwrap();
+        assert_eq!(stroke_layout(&database), Some(layout));
+        assert_eq!(database[0].1[0].len(), 15);
+        assert_eq!(database[0].1[0][11], 1.0);
+        assert_eq!(database[1].1[0][11], 0.0);
+
+        let path = std::env::temp_dir().join("juststrokes_features_test.csv");
+        crate::csv_data::save_graphics_csv(&path, &database).unwrap();
+        let loaded = crate::csv_data::load_graphics_csv(&path).expect("Failed to load database");
+        assert_eq!(loaded, database);
+        let _ = fs::remove_file(&path);
+
+        // The corner costs the arc a feature penalty
+        let matcher = crate::Matcher::new(loaded.clone(), None);
+        let input = vec![vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]];
+        assert_eq!(matcher.match_strokes(&input, 1), vec!["㇕"]);
+        let explanation = matcher.explain(&input, "㇁").unwrap();
+        assert!(explanation.feature_penalty > 0.0);
+
+        let options = crate::MatcherOptions {
+            feature_weight: 0.0,
+            ..Default::default()
+        };
+        let matcher = crate::Matcher::new(loaded, Some(options));
+        assert_eq!(matcher.explain(&input, "㇁").unwrap().feature_penalty, 0.0);
+    }
 }
@@ -0,0 +1,175 @@
+use crate::{Point, Stroke, get_aabb};
+use serde::{Deserialize, Serialize};
+
+/// Per-input slant and rotation correction, estimated from the strokes themselves
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DeskewOptions {
+    /// Undo rotation estimated from near-horizontal strokes
+    pub rotate: bool,
+    /// Undo slant estimated from near-vertical strokes (after rotation)
+    pub deskew: bool,
+    /// Strokes within this many degrees of horizontal or vertical are measured
+    pub tolerance_degrees: f64,
+    /// Largest rotation that will be undone, in degrees
+    pub max_rotation_degrees: f64,
+    /// Largest slant that will be undone, in degrees
+    pub max_slant_degrees: f64,
+}
+
+impl Default for DeskewOptions {
+    fn default() -> Self {
+        Self {
+            rotate: false,
+            deskew: false,
+            tolerance_degrees: 25.0,
+            max_rotation_degrees: 15.0,
+            max_slant_degrees: 20.0,
+        }
+    }
+}
+
+impl DeskewOptions {
+    /// True when at least one correction is switched on
+    pub fn is_enabled(&self) -> bool {
+        self.rotate || self.deskew
+    }
+}
+
+/// Length-weighted mean deviation of chords from one axis, in degrees
+/// `deviation` maps a chord (dx, dy) to its signed angle from the axis;
+/// chords further than `tolerance` from the axis are ignored
+fn mean_deviation(strokes: &[Stroke], deviation: impl Fn(f64, f64) -> f64, tolerance: f64) -> f64 {
+    let (mut sum, mut weight) = (0.0, 0.0);
+    for stroke in strokes {
+        let (Some(first), Some(last)) = (stroke.first(), stroke.last()) else {
+            continue;
+        };
+        let (dx, dy) = (last[0] - first[0], last[1] - first[1]);
+        let length = dx.hypot(dy);
+        if length == 0.0 {
+            continue;
+        }
+        let angle = deviation(dx, dy);
+        if angle.abs() <= tolerance {
+            sum += angle * length;
+            weight += length;
+        }
+    }
+    if weight > 0.0 { sum / weight } else { 0.0 }
+}
+
+/// Rotation of near-horizontal strokes in degrees (positive = clockwise on screen,
+/// i.e. the right end lower)
+pub fn estimate_rotation(strokes: &[Stroke], tolerance_degrees: f64) -> f64 {
+    mean_deviation(
+        strokes,
+        // Direction-independent: a stroke drawn right to left counts the same
+        |dx, dy| {
+            let (dx, dy) = if dx < 0.0 { (-dx, -dy) } else { (dx, dy) };
+            dy.atan2(dx).to_degrees()
+        },
+        tolerance_degrees,
+    )
+}
+
+/// Slant of near-vertical strokes in degrees (positive = bottom leaning right)
+pub fn estimate_slant(strokes: &[Stroke], tolerance_degrees: f64) -> f64 {
+    mean_deviation(
+        strokes,
+        |dx, dy| {
+            let (dx, dy) = if dy < 0.0 { (-dx, -dy) } else { (dx, dy) };
+            dx.atan2(dy).to_degrees()
+        },
+        tolerance_degrees,
+    )
+}
+
+/// Undo the estimated rotation and slant around the centre of the input
+pub fn deskew_strokes(strokes: &[Stroke], opts: &DeskewOptions) -> Vec<Stroke> {
+    let aabb = get_aabb(strokes);
+    let center: Point = [
+        (aabb[0][0] + aabb[1][0]) / 2.0,
+        (aabb[0][1] + aabb[1][1]) / 2.0,
+    ];
+    let mut result = strokes.to_vec();
+
+    if opts.rotate {
+        let rotation = estimate_rotation(&result, opts.tolerance_degrees)
+            .clamp(-opts.max_rotation_degrees, opts.max_rotation_degrees);
+        let (sin, cos) = (-rotation.to_radians()).sin_cos();
+        transform(&mut result, |p| {
+            let (x, y) = (p[0] - center[0], p[1] - center[1]);
+            [center[0] + x * cos - y * sin, center[1] + x * sin + y * cos]
+        });
+    }
+
+    if opts.deskew {
+        let slant = estimate_slant(&result, opts.tolerance_degrees)
+            .clamp(-opts.max_slant_degrees, opts.max_slant_degrees);
+        let shear = slant.to_radians().tan();
+        transform(&mut result, |p| [p[0] - shear * (p[1] - center[1]), p[1]]);
+    }
+
+    result
+}
+
+fn transform(strokes: &mut [Stroke], f: impl Fn(Point) -> Point) {
+    for point in strokes.iter_mut().flatten() {
+        *point = f(*point);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 十 rotated clockwise by `degrees` around (50, 50)
+    fn rotated_shi(degrees: f64) -> Vec<Stroke> {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let rotate = |x: f64, y: f64| {
+            let (x, y) = (x - 50.0, y - 50.0);
+            [50.0 + x * cos - y * sin, 50.0 + x * sin + y * cos]
+        };
+        vec![
+            vec![rotate(10.0, 50.0), rotate(90.0, 50.0)],
+            vec![rotate(50.0, 10.0), rotate(50.0, 90.0)],
+        ]
+    }
+
+    #[test]
+    fn test_undo_rotation() {
+        let strokes = rotated_shi(10.0);
+        assert!((estimate_rotation(&strokes, 25.0) - 10.0).abs() < 1e-9);
+
+        let opts = DeskewOptions {
+            rotate: true,
+            ..Default::default()
+        };
+        let corrected = deskew_strokes(&strokes, &opts);
+        assert!((corrected[0][0][1] - corrected[0][1][1]).abs() < 1e-9);
+        assert!((corrected[1][0][0] - corrected[1][1][0]).abs() < 1e-9);
+
+        // Correction is limited
+        let limited = deskew_strokes(&rotated_shi(20.0), &opts);
+        assert!(estimate_rotation(&limited, 25.0) > 4.9);
+    }
+
+    #[test]
+    fn test_undo_slant() {
+        // Horizontal stroke, then a vertical stroke leaning right at the bottom
+        let strokes = vec![
+            vec![[10.0, 50.0], [90.0, 50.0]],
+            vec![[45.0, 10.0], [55.0, 90.0]],
+        ];
+        assert!(estimate_slant(&strokes, 25.0) > 7.0);
+        assert_eq!(estimate_rotation(&strokes, 25.0), 0.0);
+
+        let opts = DeskewOptions {
+            deskew: true,
+            ..Default::default()
+        };
+        let corrected = deskew_strokes(&strokes, &opts);
+        assert!((corrected[1][0][0] - corrected[1][1][0]).abs() < 1e-9);
+        assert_eq!(corrected[0], strokes[0]);
+    }
+}
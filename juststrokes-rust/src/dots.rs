@@ -0,0 +1,104 @@
+use crate::{StrokeProcessed, encoded_points};
+use serde::{Deserialize, Serialize};
+
+/// Special scoring for dots and other tiny input strokes (off by default)
+/// A tap resamples to a few nearly identical points whose angle and length
+/// are noise, so a tap compared with a template dot is scored by position
+/// instead; against longer template strokes it is scored as usual
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DotOptions {
+    pub enabled: bool,
+    /// Largest dot extent as a fraction of the character's bounding box diagonal
+    pub max_size: f64,
+    /// Share of the angle penalty kept for dots
+    pub angle_weight: f64,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size: 0.1,
+            angle_weight: 0.25,
+        }
+    }
+}
+
+/// Diagonal of the bounding box of the sampled points of some strokes
+fn diagonal<'a>(strokes: impl IntoIterator<Item = &'a StrokeProcessed>) -> f64 {
+    let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+    for stroke in strokes {
+        for p in stroke[..2 * encoded_points(stroke)].chunks(2) {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+    }
+    (max[0] - min[0]).hypot(max[1] - min[1])
+}
+
+/// Bounding box diagonal of a character's preprocessed strokes
+/// Under canvas normalization this is the drawn size, not the coordinate space
+pub fn character_diagonal(strokes: &[StrokeProcessed]) -> f64 {
+    diagonal(strokes)
+}
+
+/// Extent of the sampled points relative to the character's bounding box
+/// diagonal (see `character_diagonal`); 0 for a character without extent
+pub fn stroke_size(stroke: &StrokeProcessed, character_diagonal: f64) -> f64 {
+    if character_diagonal <= 0.0 {
+        return 0.0;
+    }
+    diagonal([stroke]) / character_diagonal
+}
+
+/// True when a preprocessed stroke is small enough to count as a dot
+pub fn is_dot(stroke: &StrokeProcessed, character_diagonal: f64, max_size: f64) -> bool {
+    stroke_size(stroke, character_diagonal) <= max_size
+}
+
+fn centroid(stroke: &StrokeProcessed) -> [f64; 2] {
+    let points = encoded_points(stroke);
+    let mut sum = [0.0; 2];
+    for p in stroke[..2 * points].chunks(2) {
+        sum[0] += p[0];
+        sum[1] += p[1];
+    }
+    [sum[0] / points as f64, sum[1] / points as f64]
+}
+
+/// Point penalty of a dot: centroid distance, scaled like the point-by-point
+/// sum so it is comparable with the penalty of ordinary strokes
+pub fn position_penalty(input: &StrokeProcessed, reference: &StrokeProcessed) -> f64 {
+    let (a, b) = (centroid(input), centroid(reference));
+    encoded_points(input) as f64 * ((a[0] - b[0]).abs() + (a[1] - b[1]).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processed_stroke;
+
+    #[test]
+    fn test_dot_detection() {
+        let tap = processed_stroke([120.0, 60.0], [123.0, 63.0]);
+        let dian = processed_stroke([110.0, 40.0], [125.0, 55.0]);
+        let heng = processed_stroke([0.0, 128.0], [255.0, 128.0]);
+
+        let diagonal = character_diagonal(&[tap.clone(), dian.clone(), heng.clone()]);
+        assert!(is_dot(&tap, diagonal, 0.1));
+        assert!(is_dot(&dian, diagonal, 0.1));
+        assert!(!is_dot(&heng, diagonal, 0.1));
+        // Sizes are relative to the character, not the coordinate space: the
+        // dot alone is the whole character
+        assert!(!is_dot(
+            &dian,
+            character_diagonal(std::slice::from_ref(&dian)),
+            0.1
+        ));
+
+        // Only the centroids count: (121.5, 61.5) against (117.5, 47.5)
+        assert_eq!(position_penalty(&tap, &dian), 4.0 * (4.0 + 14.0));
+    }
+}
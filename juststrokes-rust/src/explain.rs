@@ -0,0 +1,153 @@
+use crate::variants::Standard;
+use crate::{Ideograph, StrokeTerms};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Per-stroke breakdown of the geometric score of one candidate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explanation {
+    pub character: Ideograph,
+    /// Stroke order variant that was compared
+    pub standard: Option<Standard>,
+    /// Terms for each input stroke against the candidate's stroke at the same index
+    pub strokes: Vec<StrokeTerms>,
+    /// Sum of all point-distance penalties
+    pub point_penalty: f64,
+    /// Sum of all angle penalties
+    pub angle_penalty: f64,
+    /// Sum of all penalties for strokes matched in reverse
+    pub reversal_penalty: f64,
+    /// Sum of all shape feature penalties (0 without stroke features)
+    pub feature_penalty: f64,
+    /// Penalty for differing stroke pair relations (see `MatcherOptions::relations`)
+    pub relation_penalty: f64,
+    /// Geometric score as computed by `score_similarity` (negated total penalty)
+    pub geometric: f64,
+}
+
+impl Explanation {
+    pub(crate) fn new(
+        character: &str,
+        standard: Option<Standard>,
+        strokes: Vec<StrokeTerms>,
+    ) -> Self {
+        let point_penalty = strokes.iter().map(|t| t.point_penalty).sum();
+        let angle_penalty = strokes.iter().map(|t| t.angle_penalty).sum();
+        let reversal_penalty = strokes.iter().map(|t| t.reversal_penalty).sum();
+        let feature_penalty = strokes.iter().map(|t| t.feature_penalty).sum();
+        let geometric = strokes.iter().fold(0.0, |score, t| {
+            score - t.point_penalty - t.angle_penalty - t.reversal_penalty - t.feature_penalty
+        });
+        Self {
+            character: character.to_string(),
+            standard,
+            strokes,
+            point_penalty,
+            angle_penalty,
+            reversal_penalty,
+            feature_penalty,
+            relation_penalty: 0.0,
+            geometric,
+        }
+    }
+
+    /// Include the penalty for stroke pair relations in the geometric score
+    pub(crate) fn with_relation_penalty(mut self, penalty: f64) -> Self {
+        self.relation_penalty = penalty;
+        self.geometric -= penalty;
+        self
+    }
+
+    /// Index of the stroke with the largest penalty
+    pub fn worst_stroke(&self) -> Option<usize> {
+        (0..self.strokes.len()).max_by(|&a, &b| {
+            self.strokes[a]
+                .penalty()
+                .total_cmp(&self.strokes[b].penalty())
+        })
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Candidate: {}", self.character)?;
+        if let Some(standard) = self.standard {
+            write!(f, " ({})", standard)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>6} {:>10} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            "stroke", "points", "angle", "length", "angle_pen", "note", "features", "total"
+        )?;
+        for (i, t) in self.strokes.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>6} {:>10.2} {:>8.0} {:>8.3} {:>10.2} {:>10} {:>10.2} {:>10.2}",
+                i,
+                t.point_penalty,
+                t.angle_difference,
+                t.length_weight,
+                t.angle_penalty,
+                if t.dot {
+                    "dot"
+                } else if t.reversed {
+                    "reversed"
+                } else {
+                    ""
+                },
+                t.feature_penalty,
+                t.penalty()
+            )?;
+        }
+        write!(
+            f,
+            "{:>6} {:>10.2} {:>8} {:>8} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+            "total",
+            self.point_penalty,
+            "",
+            "",
+            self.angle_penalty,
+            self.reversal_penalty,
+            self.feature_penalty,
+            -self.geometric
+        )?;
+        if self.relation_penalty != 0.0 {
+            write!(f, "\nstroke relations: {:.2}", self.relation_penalty)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Matcher, processed_stroke};
+
+    #[test]
+    fn test_explain_matches_score() {
+        let horizontal = processed_stroke([0.0, 128.0], [255.0, 128.0]);
+        let vertical = processed_stroke([128.0, 0.0], [128.0, 255.0]);
+        let tilted = processed_stroke([0.0, 0.0], [255.0, 255.0]);
+        let matcher = Matcher::new(
+            vec![("十".to_string(), vec![horizontal.clone(), vertical.clone()])],
+            None,
+        );
+
+        let input = vec![horizontal, tilted];
+        let explanation = matcher.explain_preprocessed(&input, "十").unwrap();
+        let scored = matcher.match_preprocessed_scored(&input, 1);
+
+        assert_eq!(explanation.strokes.len(), 2);
+        assert_eq!(explanation.strokes[0].penalty(), 0.0);
+        assert_eq!(explanation.geometric, scored[0].geometric);
+        assert_eq!(explanation.worst_stroke(), Some(1));
+        assert_eq!(
+            explanation.point_penalty + explanation.angle_penalty,
+            -explanation.geometric
+        );
+
+        // Wrong stroke count or unknown character cannot be explained
+        assert!(matcher.explain_preprocessed(&input[..1], "十").is_none());
+        assert!(matcher.explain_preprocessed(&input, "土").is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::cleanup::simplify;
+use crate::{NUM_POSSIBLE_ENCODED_VALUE, Point, Stroke};
+use std::f64::consts::PI;
+
+/// Number of shape features appended to a preprocessed stroke
+/// Layout: [turning, corners, corner_position, start_direction, end_direction]
+/// The odd count keeps feature strokes distinguishable from plain ones by length
+pub const NUM_STROKE_FEATURES: usize = 5;
+
+/// Vertex detection tolerance in normalized units (about 3% of the
+/// coordinate space, cf. `get_vertex` in tomoe)
+const VERTEX_TOLERANCE: f64 = 8.0;
+
+/// Smallest turn at a vertex that counts as a sharp corner (45 degrees)
+const CORNER_ANGLE: f64 = PI / 4.0;
+
+/// Penalty per differing corner, in encoded angle units
+const CORNER_COUNT_WEIGHT: f64 = 32.0;
+
+/// Encode an angle in radians as [0, 256), the same way as the stroke angle
+fn encode_angle(angle: f64) -> f64 {
+    let n = NUM_POSSIBLE_ENCODED_VALUE as f64;
+    ((((angle + PI) * n) / (2.0 * PI)).round()).rem_euclid(n)
+}
+
+/// Signed turn from direction `a` to direction `b`, in (-PI, PI]
+fn turn(a: f64, b: f64) -> f64 {
+    let d = (b - a).rem_euclid(2.0 * PI);
+    if d > PI { d - 2.0 * PI } else { d }
+}
+
+fn direction(p0: Point, p1: Point) -> f64 {
+    (p1[1] - p0[1]).atan2(p1[0] - p0[0])
+}
+
+/// Shape features of a stroke in normalized [0, 255] coordinates
+/// - turning: total absolute turning of the polyline (256 = one full turn)
+/// - corners: number of vertices turning by at least 45 degrees
+/// - corner_position: arc-length position of the first corner (0-255, 0 if none)
+/// - start_direction, end_direction: encoded directions of the first and last
+///   segment of the vertex polyline
+pub fn stroke_features(stroke: &Stroke) -> [f64; NUM_STROKE_FEATURES] {
+    let mut points: Stroke = stroke.clone();
+    points.dedup();
+    if points.len() < 2 {
+        return [0.0; NUM_STROKE_FEATURES];
+    }
+
+    let n = NUM_POSSIBLE_ENCODED_VALUE as f64;
+
+    // Vertices as in tomoe's get_vertex: recursive split at the farthest point
+    // Measuring on vertices keeps pen jitter out of the turning total
+    let vertices = simplify(&points, VERTEX_TOLERANCE);
+    let directions: Vec<f64> = vertices.windows(2).map(|w| direction(w[0], w[1])).collect();
+    let turning: f64 = directions.windows(2).map(|w| turn(w[0], w[1]).abs()).sum();
+    let length: f64 = points
+        .windows(2)
+        .map(|w| (w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1]))
+        .sum();
+
+    let mut corners = 0;
+    let mut corner_position = 0.0;
+    for i in 1..directions.len() {
+        if turn(directions[i - 1], directions[i]).abs() >= CORNER_ANGLE {
+            if corners == 0 && length > 0.0 {
+                corner_position = (arc_position(&points, vertices[i]) / length * 255.0).round();
+            }
+            corners += 1;
+        }
+    }
+
+    [
+        (turning * n / (2.0 * PI)).round(),
+        corners as f64,
+        corner_position,
+        encode_angle(directions[0]),
+        encode_angle(directions[directions.len() - 1]),
+    ]
+}
+
+/// Arc length from the start of `points` to the vertex `target`
+fn arc_position(points: &Stroke, target: Point) -> f64 {
+    let mut travelled = 0.0;
+    for w in points.windows(2) {
+        if w[0] == target {
+            return travelled;
+        }
+        travelled += (w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1]);
+    }
+    travelled
+}
+
+/// Features of the same stroke drawn from end to start
+/// With several corners the reversed first corner is the original last one,
+/// whose position is not stored; the first corner's position is mirrored instead
+pub fn reverse_features(features: &[f64]) -> [f64; NUM_STROKE_FEATURES] {
+    let half = (NUM_POSSIBLE_ENCODED_VALUE / 2) as f64;
+    let n = NUM_POSSIBLE_ENCODED_VALUE as f64;
+    let corner_position = if features[1] > 0.0 {
+        255.0 - features[2]
+    } else {
+        0.0
+    };
+    [
+        features[0],
+        features[1],
+        corner_position,
+        (features[4] + half) % n,
+        (features[3] + half) % n,
+    ]
+}
+
+/// Difference of two feature blocks, scaled by `weight` (see
+/// `MatcherOptions::feature_weight`)
+#[inline]
+pub fn feature_penalty(a: &[f64], b: &[f64], weight: f64) -> f64 {
+    let circular = |x: f64, y: f64| {
+        let c = (x - y).abs();
+        c.min(NUM_POSSIBLE_ENCODED_VALUE as f64 - c)
+    };
+    let corner_position = if a[1] > 0.0 && b[1] > 0.0 {
+        (a[2] - b[2]).abs()
+    } else {
+        0.0
+    };
+    weight
+        * ((a[0] - b[0]).abs()
+            + CORNER_COUNT_WEIGHT * (a[1] - b[1]).abs()
+            + corner_position
+            + circular(a[3], b[3])
+            + circular(a[4], b[4]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stroke_features() {
+        // Straight horizontal stroke: no turning, no corners, heading right
+        let straight = vec![[0.0, 128.0], [128.0, 128.0], [255.0, 128.0]];
+        let features = stroke_features(&straight);
+        assert_eq!(features, [0.0, 0.0, 0.0, 128.0, 128.0]);
+
+        // ㇕: right, then down, with the corner halfway along
+        let bent = vec![[0.0, 0.0], [128.0, 0.0], [255.0, 0.0], [255.0, 255.0]];
+        let features = stroke_features(&bent);
+        assert_eq!(features[0], 64.0);
+        assert_eq!(features[1], 1.0);
+        assert_eq!(features[2], 128.0);
+        assert_eq!(features[3], 128.0);
+        assert_eq!(features[4], 192.0);
+
+        let weight = crate::MatcherOptions::default().feature_weight;
+        assert_eq!(feature_penalty(&features, &features, weight), 0.0);
+        assert!(
+            feature_penalty(&features, &stroke_features(&straight), weight)
+                > CORNER_COUNT_WEIGHT * weight
+        );
+
+        // Same up to rounding of the corner position
+        let reversed: Stroke = bent.iter().rev().copied().collect();
+        assert!(
+            feature_penalty(
+                &reverse_features(&features),
+                &stroke_features(&reversed),
+                weight
+            ) <= 1.0
+        );
+    }
+}
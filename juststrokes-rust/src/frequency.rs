@@ -0,0 +1,123 @@
+use crate::Ideograph;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Character usage counts for the frequency prior
+#[derive(Debug, Clone, Default)]
+pub struct FrequencyTable {
+    counts: HashMap<Ideograph, f64>,
+    max_count: f64,
+}
+
+impl FrequencyTable {
+    /// Build a table from (character, count) pairs
+    pub fn from_counts<I: IntoIterator<Item = (Ideograph, f64)>>(counts: I) -> Self {
+        let mut table = Self::default();
+        for (character, count) in counts {
+            table.insert(character, count);
+        }
+        table
+    }
+
+    /// Add occurrences of a character
+    pub fn insert(&mut self, character: Ideograph, count: f64) {
+        let total = self.counts.entry(character).or_default();
+        *total += count;
+        self.max_count = self.max_count.max(*total);
+    }
+
+    /// Raw count for a character (0 if unknown)
+    pub fn count(&self, character: &str) -> f64 {
+        self.counts.get(character).copied().unwrap_or(0.0)
+    }
+
+    /// Log frequency relative to the most common character: 0 for the most
+    /// common, negative for rarer ones; unknown characters count as half an
+    /// occurrence. A table without positive counts gives no prior.
+    #[inline]
+    pub fn log_prior(&self, character: &str) -> f64 {
+        if self.counts.is_empty() || self.max_count <= 0.0 {
+            return 0.0;
+        }
+        (self.count(character).max(0.5) / self.max_count).ln()
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+}
+
+/// Load a character frequency list
+/// Each line holds a character followed by its count, separated by tabs or
+/// commas; a leading rank column (as in Jun Da's list) and trailing columns are
+/// ignored. Lines without a count are ranked by position (Zipf's law); counts
+/// must be positive.
+pub fn load_frequency_csv<P: AsRef<Path>>(
+    path: P,
+) -> Result<FrequencyTable, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    let reader = BufReader::new(file);
+    let mut table = FrequencyTable::default();
+    let mut rank = 0;
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split(['\t', ',']).map(str::trim);
+        let Some(character) = fields.find(|f| !f.is_empty() && f.parse::<f64>().is_err()) else {
+            continue;
+        };
+
+        rank += 1;
+        let count = match fields.next().map(str::parse::<f64>) {
+            Some(Ok(count)) => count,
+            _ => 1e6 / rank as f64,
+        };
+        if !(count > 0.0 && count.is_finite()) {
+            return Err(format!("Invalid count {} for {}", count, character).into());
+        }
+        table.insert(character.to_string(), count);
+    }
+
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_frequency_csv() {
+        let path = std::env::temp_dir().join("juststrokes_frequency_test.csv");
+        fs::write(
+            &path,
+            "# rank\tchar\tcount\n1\t的\t7922684\t4.09\tde\n2\t一\t3050722\t5.67\tyi1\n内,1200\n內,15\n",
+        )
+        .unwrap();
+
+        let table = load_frequency_csv(&path).expect("Failed to load frequency list");
+        assert_eq!(table.len(), 4);
+        assert_eq!(table.count("一"), 3050722.0);
+        assert_eq!(table.count("内"), 1200.0);
+        assert_eq!(table.log_prior("的"), 0.0);
+        assert!(table.log_prior("内") > table.log_prior("內"));
+        assert!(table.log_prior("內") > table.log_prior("龘"));
+
+        // Counts must be positive; a table of zeros gives no prior
+        fs::write(&path, "的\t0\n").unwrap();
+        assert!(load_frequency_csv(&path).is_err());
+        let zeros = FrequencyTable::from_counts([("的".to_string(), 0.0)]);
+        assert_eq!(zeros.log_prior("的"), 0.0);
+
+        let _ = fs::remove_file(&path);
+    }
+}
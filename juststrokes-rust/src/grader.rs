@@ -0,0 +1,478 @@
+use crate::adaptation::Distortion;
+use crate::variants::{Standard, Template};
+use crate::{
+    Matcher, NUM_POSSIBLE_ENCODED_VALUE, Point, Stroke, StrokeProcessed, StrokeTerms,
+    encode_strokes, encoded_points, get_aabb, normalize_aabb, preprocess_strokes,
+};
+use serde::{Deserialize, Serialize};
+
+/// Thresholds used to judge a stroke
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GradingOptions {
+    /// Largest accepted angle difference in encoded units (256 = full turn)
+    pub angle_tolerance: f64,
+    /// Largest accepted mean coordinate difference in normalized [0, 255] space
+    pub position_tolerance: f64,
+}
+
+impl Default for GradingOptions {
+    fn default() -> Self {
+        Self {
+            angle_tolerance: 24.0,
+            position_tolerance: 40.0,
+        }
+    }
+}
+
+/// Judgement of one drawn stroke
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verdict {
+    Correct,
+    /// Right stroke, drawn from end to start
+    WrongDirection,
+    /// Looks like a later stroke of the character
+    WrongOrder {
+        matches_stroke: usize,
+    },
+    /// Right shape and direction, wrong place
+    Misplaced,
+    /// Does not resemble the expected stroke
+    WrongShape,
+    /// The character has no more strokes
+    Extra,
+}
+
+/// How far a drawn stroke is from the expected one
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Deviation {
+    /// Mean absolute coordinate difference of the sampled points
+    pub mean_point_distance: f64,
+    /// Direction difference in degrees (0-180)
+    pub angle_degrees: f64,
+    /// Drawn start point minus expected start point
+    pub start_offset: Point,
+    /// Drawn end point minus expected end point
+    pub end_offset: Point,
+    /// Drawn length divided by expected length
+    pub length_ratio: f64,
+}
+
+/// Grade of one drawn stroke
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokeGrade {
+    pub index: usize,
+    pub verdict: Verdict,
+    /// Missing for extra strokes
+    pub deviation: Option<Deviation>,
+    pub terms: Option<StrokeTerms>,
+}
+
+/// Where and how the next stroke should be drawn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokeHint {
+    pub index: usize,
+    /// Sampled points of the expected stroke in the character's [0, 255] space
+    pub points: Vec<Point>,
+    /// Writing direction in degrees (0 = right, 90 = down)
+    pub direction_degrees: f64,
+}
+
+/// Result of grading the strokes drawn so far
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GradeReport {
+    /// Stroke order variant the strokes were graded against
+    pub standard: Option<Standard>,
+    pub strokes: Vec<StrokeGrade>,
+    /// Next stroke to draw: the first incorrect one, or the first undrawn one
+    pub next_stroke: Option<StrokeHint>,
+    /// All strokes drawn and correct
+    pub complete: bool,
+}
+
+impl GradeReport {
+    fn correct_count(&self) -> usize {
+        self.strokes
+            .iter()
+            .filter(|s| s.verdict == Verdict::Correct)
+            .count()
+    }
+}
+
+/// Grades handwriting stroke by stroke against a target character
+pub struct StrokeGrader<'a> {
+    matcher: &'a Matcher,
+    templates: Vec<&'a Template>,
+    options: GradingOptions,
+}
+
+impl<'a> StrokeGrader<'a> {
+    /// Grader for `character`; None if the database does not contain it
+    /// Only the stroke orders the matcher selects under
+    /// `MatcherOptions::standard` are graded against
+    pub fn new(matcher: &'a Matcher, character: &str, options: GradingOptions) -> Option<Self> {
+        let templates: Vec<&Template> = matcher
+            .medians
+            .iter()
+            .zip(&matcher.selected)
+            .filter(|(t, selected)| **selected && t.character == character)
+            .map(|(t, _)| t)
+            .collect();
+        if templates.is_empty() {
+            return None;
+        }
+        Some(Self {
+            matcher,
+            templates,
+            options,
+        })
+    }
+
+    /// Number of strokes in the target character (first selected stroke order)
+    pub fn stroke_count(&self) -> usize {
+        self.templates[0].strokes.len()
+    }
+
+    /// Grade the strokes drawn so far against the best-fitting stroke order variant
+    pub fn grade(&self, strokes: &[Stroke]) -> GradeReport {
+        self.templates
+            .iter()
+            .map(|template| self.grade_template(strokes, template))
+            .reduce(|best, report| {
+                if report.correct_count() > best.correct_count() {
+                    report
+                } else {
+                    best
+                }
+            })
+            .expect("grader has at least one template")
+    }
+
+    fn grade_template(&self, strokes: &[Stroke], template: &Template) -> GradeReport {
+        let reference = &template.strokes;
+        // Empty strokes cannot be graded; they are reported as extra and keep
+        // their place so report indexes match the caller's strokes
+        let drawn: Vec<usize> = (0..strokes.len())
+            .filter(|&i| !strokes[i].is_empty())
+            .collect();
+        let compared = drawn.len().min(reference.len());
+
+        let mut grades: Vec<StrokeGrade> = (0..strokes.len())
+            .map(|index| StrokeGrade {
+                index,
+                verdict: Verdict::Extra,
+                deviation: None,
+                terms: None,
+            })
+            .collect();
+        let mut next = compared;
+        if compared > 0 {
+            // Normalize the drawn strokes and the same number of template strokes
+            // the same way, so partial characters are compared in a common frame;
+            // the user's learned distortion is not applied, since grading judges
+            // the handwriting itself
+            let raw: Vec<Stroke> = drawn.iter().map(|&i| strokes[i].clone()).collect();
+            let input = self.normalize(&raw);
+            let expected = self.normalized_reference(reference, compared);
+
+            for i in 0..compared {
+                let terms = self.matcher.scoring().terms(&input[i], &expected[i]);
+                let grade = &mut grades[drawn[i]];
+                grade.verdict = self.verdict(i, &input[i], &terms, &expected);
+                grade.deviation = Some(deviation(&input[i], &expected[i], &terms));
+                grade.terms = Some(terms);
+                if grade.verdict != Verdict::Correct {
+                    next = next.min(i);
+                }
+            }
+        }
+        let complete = next == reference.len()
+            && grades.len() == reference.len()
+            && grades.iter().all(|g| g.verdict == Verdict::Correct);
+
+        GradeReport {
+            standard: template.standard,
+            strokes: grades,
+            next_stroke: reference.get(next).map(|stroke| hint(next, stroke)),
+            complete,
+        }
+    }
+
+    /// Normalize strokes the way the matcher does, without cleanup, deskewing
+    /// or the user's learned distortion
+    fn normalize(&self, strokes: &[Stroke]) -> Vec<StrokeProcessed> {
+        preprocess_strokes(
+            strokes,
+            self.matcher.layout,
+            &self.matcher.params,
+            &Distortion::default(),
+            None,
+        )
+    }
+
+    /// Re-normalize the template strokes in the frame the first `count` of
+    /// them would have if drawn alone, so that drawn strokes and later
+    /// template strokes are compared at the same scale
+    fn normalized_reference(
+        &self,
+        reference: &[StrokeProcessed],
+        count: usize,
+    ) -> Vec<StrokeProcessed> {
+        let raw: Vec<Stroke> = reference.iter().map(sampled_points).collect();
+        let params = &self.matcher.params;
+        let frame = normalize_aabb(get_aabb(&raw[..count]), params.max_ratio, params.min_width);
+        encode_strokes(&raw, frame, self.matcher.layout)
+    }
+
+    fn verdict(
+        &self,
+        index: usize,
+        input: &StrokeProcessed,
+        terms: &StrokeTerms,
+        expected: &[StrokeProcessed],
+    ) -> Verdict {
+        let angle_idx = 2 * encoded_points(input);
+        let tolerance = self.options.angle_tolerance;
+
+        if terms.angle_difference > tolerance {
+            let reversed = circular_difference(
+                input[angle_idx] + (NUM_POSSIBLE_ENCODED_VALUE / 2) as f64,
+                expected[index][angle_idx],
+            );
+            if reversed <= tolerance {
+                return Verdict::WrongDirection;
+            }
+
+            // Compare shape only: a later stroke with the same direction and similar length
+            let later = (index + 1..expected.len()).find(|&j| {
+                circular_difference(input[angle_idx], expected[j][angle_idx]) <= tolerance
+                    && length_ratio(input, &expected[j]).is_some_and(|r| (0.5..=2.0).contains(&r))
+            });
+            return match later {
+                Some(j) => Verdict::WrongOrder { matches_stroke: j },
+                None => Verdict::WrongShape,
+            };
+        }
+
+        let mean_point_distance = terms.point_penalty / (2 * encoded_points(input)) as f64;
+        if mean_point_distance > self.options.position_tolerance {
+            return Verdict::Misplaced;
+        }
+
+        Verdict::Correct
+    }
+}
+
+/// Circular distance between two encoded angles
+fn circular_difference(a: f64, b: f64) -> f64 {
+    let n = NUM_POSSIBLE_ENCODED_VALUE as f64;
+    let c = (a - b).rem_euclid(n);
+    c.min(n - c)
+}
+
+/// Ratio of encoded stroke lengths; None for a zero-length reference
+fn length_ratio(input: &StrokeProcessed, reference: &StrokeProcessed) -> Option<f64> {
+    let length_idx = 2 * encoded_points(input) + 1;
+    (reference[length_idx] > 0.0).then(|| input[length_idx] / reference[length_idx])
+}
+
+/// Sampled points of a preprocessed stroke
+pub(crate) fn sampled_points(stroke: &StrokeProcessed) -> Stroke {
+    (0..encoded_points(stroke))
+        .map(|i| [stroke[2 * i], stroke[2 * i + 1]])
+        .collect()
+}
+
+fn deviation(
+    input: &StrokeProcessed,
+    expected: &StrokeProcessed,
+    terms: &StrokeTerms,
+) -> Deviation {
+    let points = encoded_points(input);
+    let last = 2 * (points - 1);
+    Deviation {
+        mean_point_distance: terms.point_penalty / (2 * points) as f64,
+        angle_degrees: terms.angle_difference * 360.0 / NUM_POSSIBLE_ENCODED_VALUE as f64,
+        start_offset: [input[0] - expected[0], input[1] - expected[1]],
+        end_offset: [
+            input[last] - expected[last],
+            input[last + 1] - expected[last + 1],
+        ],
+        length_ratio: length_ratio(input, expected).unwrap_or(1.0),
+    }
+}
+
+fn hint(index: usize, stroke: &StrokeProcessed) -> StrokeHint {
+    let points = sampled_points(stroke);
+    let (first, last) = (points[0], points[points.len() - 1]);
+    StrokeHint {
+        index,
+        direction_degrees: (last[1] - first[1]).atan2(last[0] - first[0]).to_degrees(),
+        points,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processed_stroke;
+
+    fn shi_matcher() -> Matcher {
+        shi_matcher_with(None)
+    }
+
+    fn shi_matcher_with(options: Option<crate::MatcherOptions>) -> Matcher {
+        // 十: horizontal stroke, then vertical stroke
+        Matcher::new(
+            vec![(
+                "十".to_string(),
+                vec![
+                    processed_stroke([0.0, 128.0], [255.0, 128.0]),
+                    processed_stroke([128.0, 0.0], [128.0, 255.0]),
+                ],
+            )],
+            options,
+        )
+    }
+
+    #[test]
+    fn test_grade_correct_strokes() {
+        let matcher = shi_matcher();
+        let grader = matcher.grader("十").unwrap();
+        assert!(matcher.grader("土").is_none());
+
+        let horizontal = vec![[10.0, 50.0], [50.0, 52.0], [90.0, 50.0]];
+        let report = grader.grade(std::slice::from_ref(&horizontal));
+        assert_eq!(report.strokes[0].verdict, Verdict::Correct);
+        assert!(!report.complete);
+
+        // Next stroke goes down through the middle
+        let next = report.next_stroke.unwrap();
+        assert_eq!(next.index, 1);
+        assert_eq!(next.direction_degrees, 90.0);
+
+        let vertical = vec![[50.0, 10.0], [50.0, 90.0]];
+        let report = grader.grade(&[horizontal, vertical]);
+        assert!(report.complete);
+        assert!(report.next_stroke.is_none());
+    }
+
+    #[test]
+    fn test_grade_mistakes() {
+        let matcher = shi_matcher();
+        let grader = matcher.grader("十").unwrap();
+
+        let backwards = vec![[90.0, 50.0], [10.0, 50.0]];
+        let report = grader.grade(&[backwards]);
+        assert_eq!(report.strokes[0].verdict, Verdict::WrongDirection);
+        assert_eq!(report.next_stroke.unwrap().index, 0);
+
+        let vertical_first = vec![[50.0, 10.0], [50.0, 90.0]];
+        let report = grader.grade(&[vertical_first]);
+        assert_eq!(
+            report.strokes[0].verdict,
+            Verdict::WrongOrder { matches_stroke: 1 }
+        );
+
+        let horizontal = vec![[10.0, 50.0], [90.0, 50.0]];
+        let vertical = vec![[50.0, 10.0], [50.0, 90.0]];
+        let report = grader.grade(&[horizontal, vertical.clone(), vertical]);
+        assert_eq!(report.strokes[2].verdict, Verdict::Extra);
+        assert!(!report.complete);
+    }
+
+    #[test]
+    fn test_grade_keeps_stroke_indexes() {
+        let mut matcher = shi_matcher();
+        let horizontal = vec![[10.0, 50.0], [90.0, 50.0]];
+        let vertical = vec![[50.0, 10.0], [50.0, 90.0]];
+        let drawn = vec![horizontal, vec![], vertical];
+        let report = matcher.grader("十").unwrap().grade(&drawn);
+        let verdicts: Vec<(usize, Verdict)> = report
+            .strokes
+            .iter()
+            .map(|g| (g.index, g.verdict))
+            .collect();
+        assert_eq!(
+            verdicts,
+            vec![
+                (0, Verdict::Correct),
+                (1, Verdict::Extra),
+                (2, Verdict::Correct)
+            ]
+        );
+        assert!(report.next_stroke.is_none());
+        assert!(!report.complete);
+
+        // A learned slant does not change the grade of the drawing
+        let mut adaptation = matcher.adaptation().clone();
+        adaptation.distortion = Distortion {
+            slant: 0.5,
+            aspect: 1.5,
+        };
+        matcher.set_adaptation(adaptation);
+        assert_eq!(matcher.grader("十").unwrap().grade(&drawn), report);
+    }
+
+    #[test]
+    fn test_wrong_order_in_prefix_frame() {
+        // Two strokes in the top-left box, the same horizontal again below
+        // them, and a long vertical far to the right
+        let raw: crate::data::RawDatabase = vec![(
+            "字".to_string(),
+            vec![
+                vec![[0.0, 0.0], [100.0, 0.0]],
+                vec![[100.0, 0.0], [100.0, 100.0]],
+                vec![[0.0, 100.0], [100.0, 100.0]],
+                vec![[255.0, 0.0], [255.0, 255.0]],
+            ],
+        )];
+        let database = crate::data::build_database(&raw, crate::StrokeLayout::default()).unwrap();
+        let matcher = Matcher::new(database, None);
+
+        // The lower horizontal drawn second has the length it has next to
+        // the first two strokes, not in the whole character
+        let drawn = vec![
+            vec![[0.0, 0.0], [50.0, 0.0]],
+            vec![[0.0, 50.0], [50.0, 50.0]],
+        ];
+        let report = matcher.grader("字").unwrap().grade(&drawn);
+        assert_eq!(report.strokes[0].verdict, Verdict::Correct);
+        assert_eq!(
+            report.strokes[1].verdict,
+            Verdict::WrongOrder { matches_stroke: 2 }
+        );
+    }
+
+    #[test]
+    fn test_grade_selected_standard() {
+        let horizontal = vec![[10.0, 50.0], [90.0, 50.0]];
+        let vertical = vec![[50.0, 10.0], [50.0, 90.0]];
+        let base = shi_matcher();
+        let japanese = Template {
+            character: "十".to_string(),
+            standard: Some(Standard::Japan),
+            strokes: base.preprocess(&[vertical.clone(), horizontal.clone()]),
+        };
+        let with_standard = |standard| {
+            let options = crate::MatcherOptions {
+                standard: Some(standard),
+                ..Default::default()
+            };
+            let mut matcher = shi_matcher_with(Some(options));
+            matcher.add_variants(vec![japanese.clone()]).unwrap();
+            matcher
+        };
+
+        let drawn = [vertical, horizontal];
+        let matcher = with_standard(Standard::Japan);
+        let report = matcher.grader("十").unwrap().grade(&drawn);
+        assert_eq!(report.standard, Some(Standard::Japan));
+        assert!(report.complete);
+
+        // The Japanese order is not graded against when another standard is set
+        let matcher = with_standard(Standard::Prc);
+        let report = matcher.grader("十").unwrap().grade(&drawn);
+        assert_eq!(report.standard, None);
+        assert!(!report.complete);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{Point, Stroke, get_aabb};
+use serde::{Deserialize, Serialize};
+
+/// Input point with timing and pressure, as reported by stylus and pointer events
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InkPoint {
+    pub x: f64,
+    pub y: f64,
+    /// Timestamp in milliseconds
+    pub t: f64,
+    /// Normalized pen pressure (0-1)
+    pub pressure: f64,
+}
+
+/// Sequence of timed points forming a single stroke
+pub type InkStroke = Vec<InkPoint>;
+
+impl From<Point> for InkPoint {
+    /// Untimed point at full pressure; timing-based steps leave such strokes alone
+    fn from(p: Point) -> Self {
+        Self {
+            x: p[0],
+            y: p[1],
+            t: 0.0,
+            pressure: 1.0,
+        }
+    }
+}
+
+impl From<InkPoint> for Point {
+    fn from(p: InkPoint) -> Self {
+        [p.x, p.y]
+    }
+}
+
+/// Convert a plain stroke into an untimed ink stroke
+pub fn from_stroke(stroke: &Stroke) -> InkStroke {
+    stroke.iter().map(|&p| InkPoint::from(p)).collect()
+}
+
+/// Drop timing and pressure
+pub fn to_stroke(stroke: &[InkPoint]) -> Stroke {
+    stroke.iter().map(|&p| Point::from(p)).collect()
+}
+
+/// Timing- and pressure-based input handling (all steps off by default)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InkOptions {
+    /// Points with lower pressure are dropped (0 keeps all points)
+    pub min_pressure: f64,
+    /// Split a stroke where the pen rested longer than this many milliseconds
+    /// (0 disables)
+    pub pause_split: f64,
+    /// Strokes shorter than this many milliseconds and smaller than `tap_size`
+    /// are treated as accidental taps and dropped (0 disables)
+    pub tap_duration: f64,
+    /// Largest tap extent as a fraction of the input's bounding box diagonal
+    pub tap_size: f64,
+    /// Merge runs of points drawn slower than `min_speed` times the stroke's
+    /// mean speed into one point
+    pub velocity_resampling: bool,
+    pub min_speed: f64,
+}
+
+impl Default for InkOptions {
+    fn default() -> Self {
+        Self {
+            min_pressure: 0.0,
+            pause_split: 0.0,
+            tap_duration: 0.0,
+            tap_size: 0.05,
+            velocity_resampling: false,
+            min_speed: 0.1,
+        }
+    }
+}
+
+/// Apply the enabled steps and reduce the ink to plain strokes for matching
+/// Steps run in the order pressure, pause split, tap removal, velocity
+/// resampling; if every stroke looks like a tap, none are dropped
+pub fn prepare_strokes(strokes: &[InkStroke], opts: &InkOptions) -> Vec<Stroke> {
+    let mut ink: Vec<InkStroke> = strokes
+        .iter()
+        .map(|stroke| {
+            stroke
+                .iter()
+                .filter(|p| p.pressure >= opts.min_pressure)
+                .copied()
+                .collect::<InkStroke>()
+        })
+        .filter(|stroke| !stroke.is_empty())
+        .collect();
+
+    if opts.pause_split > 0.0 {
+        ink = ink
+            .iter()
+            .flat_map(|stroke| split_at_pauses(stroke, opts.pause_split))
+            .collect();
+    }
+
+    if opts.tap_duration > 0.0 && !ink.is_empty() {
+        let plain: Vec<Stroke> = ink.iter().map(|s| to_stroke(s)).collect();
+        let aabb = get_aabb(&plain);
+        let max_size = opts.tap_size * (aabb[1][0] - aabb[0][0]).hypot(aabb[1][1] - aabb[0][1]);
+        let kept: Vec<InkStroke> = ink
+            .iter()
+            .filter(|stroke| !is_tap(stroke, opts.tap_duration, max_size))
+            .cloned()
+            .collect();
+        if !kept.is_empty() {
+            ink = kept;
+        }
+    }
+
+    ink.iter()
+        .map(|stroke| {
+            if opts.velocity_resampling {
+                to_stroke(&resample_by_velocity(stroke, opts.min_speed))
+            } else {
+                to_stroke(stroke)
+            }
+        })
+        .collect()
+}
+
+/// Time from the first to the last point; 0 for untimed strokes
+pub fn duration(stroke: &[InkPoint]) -> f64 {
+    match (stroke.first(), stroke.last()) {
+        (Some(first), Some(last)) => last.t - first.t,
+        _ => 0.0,
+    }
+}
+
+/// Short in both time and space; untimed strokes are never taps
+pub fn is_tap(stroke: &[InkPoint], max_duration: f64, max_size: f64) -> bool {
+    let elapsed = duration(stroke);
+    if elapsed <= 0.0 || elapsed >= max_duration {
+        return false;
+    }
+    let aabb = get_aabb(&[to_stroke(stroke)]);
+    (aabb[1][0] - aabb[0][0]).max(aabb[1][1] - aabb[0][1]) <= max_size
+}
+
+/// Split a stroke wherever consecutive points are more than `max_gap` ms apart
+pub fn split_at_pauses(stroke: &[InkPoint], max_gap: f64) -> Vec<InkStroke> {
+    let mut parts: Vec<InkStroke> = Vec::new();
+    let mut current: InkStroke = Vec::new();
+    for &point in stroke {
+        if let Some(last) = current.last()
+            && point.t - last.t > max_gap
+        {
+            parts.push(std::mem::take(&mut current));
+        }
+        current.push(point);
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+/// Collapse runs of slow points (below `min_speed` times the mean speed) into
+/// their centroid, so lingering jitter does not pull the arc-length resampling
+pub fn resample_by_velocity(stroke: &[InkPoint], min_speed: f64) -> InkStroke {
+    let elapsed = duration(stroke);
+    if stroke.len() < 3 || elapsed <= 0.0 {
+        return stroke.to_vec();
+    }
+    let length: f64 = stroke
+        .windows(2)
+        .map(|w| (w[1].x - w[0].x).hypot(w[1].y - w[0].y))
+        .sum();
+    let threshold = min_speed * length / elapsed;
+
+    let mut result: InkStroke = vec![stroke[0]];
+    let mut run: Vec<InkPoint> = Vec::new();
+    for w in stroke.windows(2) {
+        let dt = w[1].t - w[0].t;
+        let speed = if dt > 0.0 {
+            (w[1].x - w[0].x).hypot(w[1].y - w[0].y) / dt
+        } else {
+            0.0
+        };
+        if speed < threshold {
+            run.push(w[1]);
+        } else {
+            flush_run(&mut result, &mut run);
+            result.push(w[1]);
+        }
+    }
+    flush_run(&mut result, &mut run);
+
+    // Keep the true end point
+    let last = stroke[stroke.len() - 1];
+    if result.last() != Some(&last) {
+        result.push(last);
+    }
+    result
+}
+
+/// Append the centroid of a run of slow points
+fn flush_run(result: &mut InkStroke, run: &mut Vec<InkPoint>) {
+    if run.is_empty() {
+        return;
+    }
+    let n = run.len() as f64;
+    result.push(InkPoint {
+        x: run.iter().map(|p| p.x).sum::<f64>() / n,
+        y: run.iter().map(|p| p.y).sum::<f64>() / n,
+        t: run[run.len() - 1].t,
+        pressure: run.iter().map(|p| p.pressure).sum::<f64>() / n,
+    });
+    run.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timed(points: &[(f64, f64, f64)]) -> InkStroke {
+        points
+            .iter()
+            .map(|&(x, y, t)| InkPoint {
+                x,
+                y,
+                t,
+                pressure: 0.5,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pause_split_and_taps() {
+        // Two strokes written without lifting the pen, then a stray tap
+        let stroke = timed(&[
+            (0.0, 50.0, 0.0),
+            (100.0, 50.0, 100.0),
+            (50.0, 0.0, 600.0),
+            (50.0, 100.0, 700.0),
+        ]);
+        let tap = timed(&[(80.0, 80.0, 900.0), (81.0, 80.0, 910.0)]);
+
+        assert_eq!(split_at_pauses(&stroke, 300.0).len(), 2);
+        assert!(is_tap(&tap, 50.0, 5.0));
+        assert!(!is_tap(&from_stroke(&vec![[1.0, 1.0]]), 50.0, 5.0));
+
+        let opts = InkOptions {
+            pause_split: 300.0,
+            tap_duration: 50.0,
+            ..Default::default()
+        };
+        let strokes = prepare_strokes(&[stroke, tap], &opts);
+        assert_eq!(strokes.len(), 2);
+        assert_eq!(strokes[1], vec![[50.0, 0.0], [50.0, 100.0]]);
+
+        // Plain points pass through unchanged
+        let plain = vec![[0.0, 0.0], [10.0, 0.0]];
+        assert_eq!(prepare_strokes(&[from_stroke(&plain)], &opts), vec![plain]);
+    }
+
+    #[test]
+    fn test_velocity_resampling() {
+        // Pen lingers and jitters around (50, 0)
+        let stroke = timed(&[
+            (0.0, 0.0, 0.0),
+            (50.0, 0.0, 10.0),
+            (51.0, 1.0, 60.0),
+            (50.0, -1.0, 110.0),
+            (100.0, 0.0, 120.0),
+        ]);
+        let resampled = resample_by_velocity(&stroke, 0.1);
+        assert_eq!(resampled.len(), 4);
+        assert_eq!(resampled[2].x, 50.5);
+        assert_eq!(resampled[3].x, 100.0);
+    }
+}
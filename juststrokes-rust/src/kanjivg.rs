@@ -0,0 +1,200 @@
+use crate::data::{RawDatabase, build_database};
+use crate::svg::{self, PathOptions};
+use crate::variants::{Standard, Template};
+use crate::{Stroke, StrokeLayout};
+use std::fs;
+use std::path::Path;
+
+/// Flatten a KanjiVG stroke path into a polyline
+/// KanjiVG draws each stroke as its centerline, one subpath of cubic Béziers
+pub fn parse_stroke_path(d: &str) -> Result<Stroke, Box<dyn std::error::Error>> {
+    let mut subpaths = svg::parse_path(d, &PathOptions::default())?;
+    match (subpaths.pop(), subpaths.is_empty()) {
+        (Some(stroke), true) => Ok(stroke),
+        (None, _) => Err(format!("Empty stroke path: {}", d).into()),
+        (Some(_), false) => Err(format!("Stroke path has several subpaths: {}", d).into()),
+    }
+}
+
+/// Strokes of one KanjiVG character, in stroke order
+/// Stroke paths are the `<path>` elements; stroke numbers are `<text>` and
+/// are skipped
+pub fn parse_kanjivg_svg(text: &str) -> Result<Vec<Stroke>, Box<dyn std::error::Error>> {
+    svg::path_data(text)?
+        .into_iter()
+        .map(parse_stroke_path)
+        .collect()
+}
+
+/// Load KanjiVG stroke paths, from a directory of per-character files
+/// (`kanji/05b57.svg`) or from the combined `kanjivg.xml`
+/// Variant files (`05b57-Kaisho.svg`) are skipped, and the 109-unit box is
+/// already in screen coordinates (y pointing down)
+pub fn load_kanjivg<P: AsRef<Path>>(path: P) -> Result<RawDatabase, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let mut result = Vec::new();
+    if path.is_dir() {
+        let mut files: Vec<_> = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        files.sort();
+        for file in files {
+            if file.extension().is_none_or(|e| e != "svg") {
+                continue;
+            }
+            let stem = file
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            let Some(character) = svg::character_of(stem) else {
+                continue;
+            };
+            let strokes = parse_kanjivg_svg(&fs::read_to_string(&file)?)
+                .map_err(|e| format!("{}: {}", file.display(), e))?;
+            result.push((character, strokes));
+        }
+        return Ok(result);
+    }
+
+    let text = fs::read_to_string(path)?;
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("<kanji ") {
+        rest = &rest[start..];
+        let end = rest
+            .find("</kanji>")
+            .ok_or("Unterminated <kanji> element")?;
+        let (element, tail) = rest.split_at(end);
+        rest = tail;
+        let tag_end = element.find('>').unwrap_or(element.len());
+        let Some(character) = svg::attribute(&element[..tag_end], "id")
+            .and_then(|id| id.strip_prefix("kvg:kanji_"))
+            .and_then(svg::character_of)
+        else {
+            continue;
+        };
+        result.push((character, parse_kanjivg_svg(element)?));
+    }
+    Ok(result)
+}
+
+/// Japanese forms as stroke-order variants tagged `Standard::Japan`, for
+/// `Matcher::add_variants`; select them with `MatcherOptions::standard`
+pub fn japanese_templates(
+    raw: &RawDatabase,
+    layout: StrokeLayout,
+) -> Result<Vec<Template>, Box<dyn std::error::Error>> {
+    Ok(build_database(raw, layout)?
+        .into_iter()
+        .map(|(character, strokes)| Template {
+            character,
+            standard: Some(Standard::Japan),
+            strokes,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Matcher, MatcherOptions};
+
+    const NI: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="109" height="109" viewBox="0 0 109 109">
+<g id="kvg:StrokePaths_04e8c" style="fill:none;stroke:#000000;stroke-width:3;">
+<g id="kvg:04e8c" kvg:element="二">
+	<path id="kvg:04e8c-s1" kvg:type="㇐" d="M26.5,31.5c1.5,0.5,3.5,0.6,5,0.5c9-0.6,31.8-3.1,45.5-3.5"/>
+	<path id="kvg:04e8c-s2" kvg:type="㇐" d="M12.8,80.6c2.3,0.6,5,0.8,7.3,0.6C34,79.5,70.5,77,92,77.2"/>
+</g>
+</g>
+<g id="kvg:StrokeNumbers_04e8c" style="font-size:8;fill:#808080">
+	<text transform="matrix(1 0 0 1 19.50 32.50)">1</text>
+	<text transform="matrix(1 0 0 1 5.50 82.50)">2</text>
+</g>
+</svg>"#;
+
+    #[test]
+    fn test_parse_kanjivg_paths() {
+        let n = PathOptions::default().curve_segments;
+        let stroke = parse_stroke_path("M10,10c0,10,20,10,20,0s20-10,20,0").unwrap();
+        assert_eq!(stroke.len(), 1 + 2 * n);
+        assert_eq!(stroke[0], [10.0, 10.0]);
+        assert_eq!(stroke[n], [30.0, 10.0]);
+        assert_eq!(stroke[2 * n], [50.0, 10.0]);
+        // Curve midpoints: below the start for c, above it for the reflected s
+        assert_eq!(stroke[n / 2], [20.0, 17.5]);
+        assert_eq!(stroke[3 * n / 2], [40.0, 2.5]);
+
+        assert_eq!(
+            parse_stroke_path("M1-2.5.5.5").unwrap(),
+            vec![[1.0, -2.5], [0.5, 0.5]]
+        );
+        assert!(parse_stroke_path("M0,0 A1,1,0,0,1,2,2").is_err());
+
+        let strokes = parse_kanjivg_svg(NI).unwrap();
+        assert_eq!(strokes.len(), 2);
+        assert_eq!(strokes[1][0], [12.8, 80.6]);
+        assert_eq!(*strokes[1].last().unwrap(), [92.0, 77.2]);
+    }
+
+    #[test]
+    fn test_japanese_forms() {
+        let dir = std::env::temp_dir().join("juststrokes_kanjivg_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("04e8c.svg"), NI).unwrap();
+        fs::write(dir.join("04e8c-Kaisho.svg"), NI).unwrap();
+        let raw = load_kanjivg(&dir).unwrap();
+        assert_eq!(raw.len(), 1);
+        assert_eq!(raw[0].0, "二");
+
+        let xml = format!(
+            "<kanjivg>\n<kanji id=\"kvg:kanji_04e8c\">{}</kanji>\n</kanjivg>",
+            NI
+        );
+        fs::write(dir.join("kanjivg.xml"), xml).unwrap();
+        assert_eq!(load_kanjivg(dir.join("kanjivg.xml")).unwrap(), raw);
+
+        // Files are parsed as strictly as kanjivg.xml: one subpath per stroke
+        fs::write(
+            dir.join("04e00.svg"),
+            r#"<svg><path d="M10,50 L50,50 M50,50 L90,50"/></svg>"#,
+        )
+        .unwrap();
+        assert!(load_kanjivg(&dir).is_err());
+        let _ = fs::remove_dir_all(&dir);
+
+        // The base form of 二 is written with its strokes the other way round
+        let heng = |y: f64| vec![[10.0, y], [90.0, y]];
+        let base: RawDatabase = vec![
+            ("二".to_string(), vec![heng(80.0), heng(30.0)]),
+            ("工".to_string(), vec![heng(20.0), heng(80.0)]),
+        ];
+        let layout = StrokeLayout::default();
+        let matcher_with = |standard: Option<Standard>| {
+            let options = MatcherOptions {
+                standard,
+                ..Default::default()
+            };
+            let mut matcher = Matcher::new(build_database(&base, layout).unwrap(), Some(options));
+            matcher
+                .add_variants(japanese_templates(&raw, layout).unwrap())
+                .unwrap();
+            matcher
+        };
+        let drawn = vec![
+            vec![[26.0, 31.0], [77.0, 29.0]],
+            vec![[13.0, 80.0], [92.0, 77.0]],
+        ];
+
+        // Only the Japanese form of 二 is matched, next to the base 工
+        let matcher = matcher_with(Some(Standard::Japan));
+        assert_eq!(matcher.selected, [false, true, true]);
+        let japanese = matcher.match_scored(&drawn, 2);
+        assert_eq!(japanese[0].character, "二");
+        assert_eq!(japanese[0].standard, Some(Standard::Japan));
+
+        // Without the Japanese form, the drawing is closest to 工
+        let chinese = matcher_with(Some(Standard::Prc)).match_scored(&drawn, 2);
+        assert_eq!(chinese[0].character, "工");
+        assert!(chinese.iter().all(|c| c.standard.is_none()));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::Ideograph;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Score multiplier applied each time the model backs off to a shorter history
+const BACKOFF_FACTOR: f64 = 0.4;
+
+/// Longest n-gram the model stores (trigram)
+const MAX_ORDER: usize = 3;
+
+/// Counts of characters that followed one history
+#[derive(Debug, Clone, Default)]
+struct Continuations {
+    total: f64,
+    counts: HashMap<Ideograph, f64>,
+}
+
+/// Character unigram/bigram/trigram model scored with stupid backoff
+#[derive(Debug, Clone, Default)]
+pub struct NgramModel {
+    unigrams: Continuations,
+    /// History of one or two characters -> following characters
+    histories: HashMap<String, Continuations>,
+}
+
+impl NgramModel {
+    /// Count all n-grams (up to trigrams) in a plain text corpus
+    pub fn from_text(text: &str) -> Self {
+        let mut model = Self::default();
+        for line in text.lines() {
+            let chars: Vec<&str> = split_chars(line).collect();
+            for end in 1..=chars.len() {
+                for order in 1..=MAX_ORDER.min(end) {
+                    model.add(&chars[end - order..end].concat(), 1.0);
+                }
+            }
+        }
+        model
+    }
+
+    /// Add occurrences of an n-gram of one to three characters
+    pub fn add(&mut self, ngram: &str, count: f64) {
+        let Some((split, _)) = ngram.char_indices().last() else {
+            return;
+        };
+        let (history, character) = ngram.split_at(split);
+        let entry = if history.is_empty() {
+            &mut self.unigrams
+        } else {
+            self.histories.entry(history.to_string()).or_default()
+        };
+        entry.total += count;
+        *entry.counts.entry(character.to_string()).or_default() += count;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unigrams.counts.is_empty() && self.histories.is_empty()
+    }
+
+    /// Prepare a scorer for characters following `context`
+    /// Only the last two characters of the context are used
+    pub fn scorer<'a>(&'a self, context: &str) -> ContextScorer<'a> {
+        let starts: Vec<usize> = context.char_indices().map(|(i, _)| i).collect();
+        let mut histories = Vec::new();
+        for order in (1..MAX_ORDER).rev() {
+            if starts.len() >= order {
+                histories.push(self.histories.get(&context[starts[starts.len() - order]..]));
+            }
+        }
+        ContextScorer {
+            model: self,
+            histories,
+        }
+    }
+
+    /// Log probability of `character` following `context`
+    pub fn log_probability(&self, context: &str, character: &str) -> f64 {
+        self.scorer(context).log_probability(character)
+    }
+}
+
+/// Language model lookups for one fixed context, longest history first
+pub struct ContextScorer<'a> {
+    model: &'a NgramModel,
+    histories: Vec<Option<&'a Continuations>>,
+}
+
+impl ContextScorer<'_> {
+    /// Stupid-backoff log score of the next character; 0 if the model is empty
+    #[inline]
+    pub fn log_probability(&self, character: &str) -> f64 {
+        if self.model.is_empty() {
+            return 0.0;
+        }
+
+        let mut backoff = 0.0;
+        for continuations in &self.histories {
+            if let Some(continuations) = continuations
+                && let Some(&count) = continuations.counts.get(character)
+            {
+                return backoff + (count / continuations.total).ln();
+            }
+            backoff += BACKOFF_FACTOR.ln();
+        }
+
+        let unigrams = &self.model.unigrams;
+        let count = unigrams.counts.get(character).copied().unwrap_or(0.0);
+        backoff + (count.max(0.5) / unigrams.total.max(1.0)).ln()
+    }
+}
+
+/// Iterate over the characters of a string as string slices
+fn split_chars(text: &str) -> impl Iterator<Item = &str> {
+    text.char_indices()
+        .map(move |(i, c)| &text[i..i + c.len_utf8()])
+}
+
+/// Load a character n-gram count file
+/// Format: ngram\tcount per line, where ngram is one to three characters
+pub fn load_ngram_counts<P: AsRef<Path>>(
+    path: P,
+) -> Result<NgramModel, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    let reader = BufReader::new(file);
+    let mut model = NgramModel::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (ngram, count) = line.split_once('\t').ok_or("Missing count column")?;
+        if ngram.chars().count() > MAX_ORDER {
+            return Err(format!("N-gram longer than {} characters: {}", MAX_ORDER, ngram).into());
+        }
+        model.add(ngram, count.trim().parse()?);
+    }
+
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_prefers_seen_continuation() {
+        let model = NgramModel::from_text("我们\n我们\n我们\n我们的\n你们\n内容\n");
+
+        // "们" follows "我" in the corpus, "内" never does
+        assert!(model.log_probability("我", "们") > model.log_probability("我", "内"));
+        assert_eq!(model.log_probability("我", "们"), 0.0);
+
+        // Trigram history is used when available
+        assert!(model.log_probability("我们", "的") > model.log_probability("你们", "的"));
+
+        // Empty model is neutral
+        assert_eq!(NgramModel::default().log_probability("我", "们"), 0.0);
+    }
+
+    #[test]
+    fn test_load_ngram_counts() {
+        let path = std::env::temp_dir().join("juststrokes_ngram_test.tsv");
+        fs::write(&path, "我\t10\n们\t8\n我们\t7\n我们的\t3\n").unwrap();
+
+        let model = load_ngram_counts(&path).expect("Failed to load n-grams");
+        assert_eq!(model.log_probability("我", "们"), 0.0);
+        assert_eq!(model.log_probability("我们", "的"), 0.0);
+        assert!(model.log_probability("", "们") < 0.0);
+
+        let _ = fs::remove_file(&path);
+    }
+}
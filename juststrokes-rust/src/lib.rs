@@ -1,9 +1,47 @@
+use adaptation::{Distortion, UserAdaptation};
+use charset::CharacterFilter;
+use cleanup::CleanupOptions;
+use deskew::DeskewOptions;
+use dots::DotOptions;
+use explain::Explanation;
+use frequency::FrequencyTable;
+use ink::{InkOptions, InkStroke};
+use language_model::{ContextScorer, NgramModel};
+use metadata::{EnrichedCandidate, MetadataStore};
+use relations::{RelationOptions, RelationPairs, StrokeRelation};
+use script::{Script, ScriptHandling, ScriptTable};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::f64::consts::PI;
+use std::sync::OnceLock;
+use stroke_type::StrokeType;
+use variants::{Standard, Template};
 
+pub mod adaptation;
+pub mod charset;
+pub mod cleanup;
+pub mod components;
 pub mod csv_data;
 pub mod data;
+pub mod deskew;
+pub mod dots;
+pub mod explain;
+pub mod features;
+pub mod frequency;
+pub mod grader;
+pub mod ink;
+pub mod kanjivg;
+pub mod language_model;
+pub mod line;
+pub mod metadata;
+pub mod overlap;
+pub mod relations;
+pub mod script;
 pub mod socket_service;
+pub mod stroke_search;
+pub mod stroke_type;
+pub mod svg;
+pub mod variants;
 
 /// 2D point in canvas coordinate space
 pub type Point = [f64; 2];
@@ -14,8 +52,10 @@ pub type Stroke = Vec<Point>;
 /// Axis-aligned bounding box represented as [min_corner, max_corner]
 pub type AABB = [Point; 2];
 
-/// Preprocessed stroke data: 4 sampled points (8 coordinates) + angle + length = 10 values
-/// Format: [x0, y0, x1, y1, x2, y2, x3, y3, angle_encoded, length_encoded]
+/// Preprocessed stroke data: N sampled points (2N coordinates) + angle + length,
+/// optionally followed by shape features (see `features`)
+/// Format: [x0, y0, x1, y1, ..., x(N-1), y(N-1), angle_encoded, length_encoded, features...]
+/// N is a database-level parameter (4 for the classic 10-value layout)
 pub type StrokeProcessed = Vec<f64>;
 
 /// Chinese character (CJK ideograph)
@@ -24,8 +64,64 @@ pub type Ideograph = String;
 /// Coordinate space size for normalization (0-255)
 const NUM_POSSIBLE_ENCODED_VALUE: usize = 256;
 
-/// Number of points to sample per stroke during preprocessing
-const NUM_ENCODED_POINTS: usize = 4;
+/// Default number of points to sample per stroke during preprocessing
+pub const NUM_ENCODED_POINTS: usize = 4;
+
+/// True when a preprocessed stroke carries shape features (odd length)
+/// Scoring reads the database's `StrokeLayout` instead
+#[inline]
+pub fn has_features(stroke: &StrokeProcessed) -> bool {
+    stroke.len() % 2 == 1
+}
+
+/// Number of sampled points encoded in a preprocessed stroke
+#[inline]
+pub fn encoded_points(stroke: &StrokeProcessed) -> usize {
+    let features = if has_features(stroke) {
+        features::NUM_STROKE_FEATURES
+    } else {
+        0
+    };
+    stroke.len().saturating_sub(2 + features) / 2
+}
+
+/// Layout of the preprocessed strokes of a database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StrokeLayout {
+    /// Sampled points per stroke
+    pub points: usize,
+    /// Shape features appended after angle and length
+    pub features: bool,
+}
+
+impl Default for StrokeLayout {
+    fn default() -> Self {
+        Self {
+            points: NUM_ENCODED_POINTS,
+            features: false,
+        }
+    }
+}
+
+impl StrokeLayout {
+    /// Layout of an existing preprocessed stroke
+    pub fn of(stroke: &StrokeProcessed) -> Self {
+        Self {
+            points: encoded_points(stroke),
+            features: has_features(stroke),
+        }
+    }
+
+    /// Number of values in a stroke with this layout
+    pub fn stroke_len(&self) -> usize {
+        let features = if self.features {
+            features::NUM_STROKE_FEATURES
+        } else {
+            0
+        };
+        2 * self.points + 2 + features
+    }
+}
 
 /// Utility functions for 2D vector operations
 struct VectorFunctions;
@@ -171,14 +267,46 @@ fn normalize_aabb(mut aabb: AABB, max_ratio: f64, min_width: f64) -> AABB {
 }
 
 /// Transform raw strokes into normalized feature vectors for matching
-/// Steps: normalize coordinates → resample → encode angle and length
-fn preprocess_strokes(strokes: &[Stroke], opts: &MatcherOptions) -> Vec<StrokeProcessed> {
+/// Steps: undo learned distortion → normalize coordinates → resample → encode angle and length
+/// `canvas` is used by the canvas-based `MatcherOptions::normalization` modes
+fn preprocess_strokes(
+    strokes: &[Stroke],
+    layout: StrokeLayout,
+    opts: &MatcherOptions,
+    distortion: &Distortion,
+    canvas: Option<Canvas>,
+) -> Vec<StrokeProcessed> {
     if strokes.is_empty() || strokes.iter().any(|s| s.is_empty()) {
         panic!("Invalid stroke data: empty strokes not allowed");
     }
 
+    // Correct the user's systematic slant and aspect before measuring the bounding box
+    let corrected: Vec<Stroke>;
+    let strokes = if distortion.is_identity() {
+        strokes
+    } else {
+        corrected = strokes
+            .iter()
+            .map(|stroke| stroke.iter().map(|&p| distortion.apply(p)).collect())
+            .collect();
+        &corrected
+    };
+
+    let aabb_after = opts
+        .normalization
+        .frame(strokes, canvas)
+        .unwrap_or_else(|| normalize_aabb(get_aabb(strokes), opts.max_ratio, opts.min_width));
+    encode_strokes(strokes, aabb_after, layout)
+}
+
+/// Project strokes from `aabb_after` onto the [0, 255] space, resample them
+/// and encode angle and length; strokes outside the box land outside the space
+fn encode_strokes(
+    strokes: &[Stroke],
+    aabb_after: AABB,
+    layout: StrokeLayout,
+) -> Vec<StrokeProcessed> {
     let side_length = NUM_POSSIBLE_ENCODED_VALUE as f64;
-    let aabb_after = normalize_aabb(get_aabb(strokes), opts.max_ratio, opts.min_width);
     let target_aabb: AABB = [[0.0, 0.0], [255.0, 255.0]];
     let project = create_normalized_project_function(aabb_after, target_aabb);
 
@@ -187,7 +315,7 @@ fn preprocess_strokes(strokes: &[Stroke], opts: &MatcherOptions) -> Vec<StrokePr
         .map(|stroke| {
             // Transform to normalized [0, 255] coordinate space
             let projected: Stroke = stroke.iter().map(|&p| project(p)).collect();
-            let stroke_processed = process_stroke(&projected, NUM_ENCODED_POINTS);
+            let stroke_processed = process_stroke(&projected, layout.points);
 
             // Compute stroke direction vector (first point to last point)
             let stroke_span = VectorFunctions::subtract(
@@ -207,52 +335,400 @@ fn preprocess_strokes(strokes: &[Stroke], opts: &MatcherOptions) -> Vec<StrokePr
             let mut result: StrokeProcessed = stroke_processed.into_iter().flatten().collect();
             result.push(angle_encoded as f64);
             result.push(length_encoded as f64);
+            if layout.features {
+                result.extend(features::stroke_features(&projected));
+            }
             result
         })
         .collect()
 }
 
-/// Compute similarity score between two stroke sequences (higher = more similar)
-/// Combines point position differences with angle and length-weighted penalties
-#[inline]
-fn score_similarity(input: &[StrokeProcessed], reference: &[StrokeProcessed]) -> f64 {
-    let mut score = 0.0;
-    const MAGIC_PER_STROKE_WEIGHT: f64 = 4.0;
-    const NUM_ENCODED_POINTS_F64: f64 = NUM_ENCODED_POINTS as f64;
-    const NUM_POSSIBLE_ENCODED_VALUE_F64: f64 = NUM_POSSIBLE_ENCODED_VALUE as f64;
+/// Encode a straight test stroke given in [0, 255] coordinates
+#[cfg(test)]
+pub(crate) fn processed_stroke(from: Point, to: Point) -> StrokeProcessed {
+    let identity: AABB = [[0.0, 0.0], [255.0, 255.0]];
+    encode_strokes(&[vec![from, to]], identity, StrokeLayout::default()).remove(0)
+}
 
-    for i in 0..input.len() {
-        let input_stroke = &input[i];
-        let ref_stroke = &reference[i];
+/// Per-stroke components of `score_similarity` (penalties are positive)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StrokeTerms {
+    /// Sum of absolute coordinate differences over the sampled points
+    pub point_penalty: f64,
+    /// Circular difference of the encoded angles (0-128)
+    pub angle_difference: f64,
+    /// Sum of both encoded lengths relative to the coordinate space
+    pub length_weight: f64,
+    /// Angle difference scaled by the length weight
+    pub angle_penalty: f64,
+    /// Input stroke was compared end to start (see `MatcherOptions::allow_reversed`)
+    pub reversed: bool,
+    /// Penalty for comparing the stroke reversed (0 when not reversed)
+    pub reversal_penalty: f64,
+    /// Input stroke was scored as a dot, by position (see `MatcherOptions::dots`)
+    pub dot: bool,
+    /// Weighted shape feature difference (0 unless both strokes have features)
+    pub feature_penalty: f64,
+}
+
+impl StrokeTerms {
+    /// Total penalty this stroke contributes to the similarity score
+    #[inline]
+    pub fn penalty(&self) -> f64 {
+        self.point_penalty + self.angle_penalty + self.reversal_penalty + self.feature_penalty
+    }
+}
+
+/// How strokes of the database layout are compared
+#[derive(Debug, Clone, Copy)]
+struct StrokeScoring {
+    layout: StrokeLayout,
+    /// See `MatcherOptions::feature_weight`
+    feature_weight: f64,
+}
+
+impl StrokeScoring {
+    /// Compare one input stroke with one reference stroke
+    #[inline]
+    fn terms(&self, input_stroke: &StrokeProcessed, ref_stroke: &StrokeProcessed) -> StrokeTerms {
+        // The default layout gets its own copy with a constant loop bound
+        match self.layout.points {
+            NUM_ENCODED_POINTS => self.terms_with(input_stroke, ref_stroke, NUM_ENCODED_POINTS),
+            points => self.terms_with(input_stroke, ref_stroke, points),
+        }
+    }
+
+    #[inline(always)]
+    fn terms_with(
+        &self,
+        input_stroke: &StrokeProcessed,
+        ref_stroke: &StrokeProcessed,
+        points: usize,
+    ) -> StrokeTerms {
+        const MAGIC_PER_STROKE_WEIGHT: f64 = 4.0;
+        const NUM_POSSIBLE_ENCODED_VALUE_F64: f64 = NUM_POSSIBLE_ENCODED_VALUE as f64;
 
         // Penalize coordinate differences for each sampled point
-        for s in 0..NUM_ENCODED_POINTS {
+        let mut point_penalty = 0.0;
+        for s in 0..points {
             let idx = 2 * s;
-            score -= (input_stroke[idx] - ref_stroke[idx]).abs();
-            score -= (input_stroke[idx + 1] - ref_stroke[idx + 1]).abs();
+            point_penalty += (input_stroke[idx] - ref_stroke[idx]).abs();
+            point_penalty += (input_stroke[idx + 1] - ref_stroke[idx + 1]).abs();
         }
 
         // Penalize angle difference (using circular distance for wraparound)
-        let angle_idx = 2 * NUM_ENCODED_POINTS;
+        let angle_idx = 2 * points;
         let c = (input_stroke[angle_idx] - ref_stroke[angle_idx]).abs();
-        let angle_similarity = c.min(NUM_POSSIBLE_ENCODED_VALUE_F64 - c);
+        let angle_difference = c.min(NUM_POSSIBLE_ENCODED_VALUE_F64 - c);
 
         // Scale angle penalty by average stroke length (longer strokes matter more)
         let length_idx = angle_idx + 1;
-        let lengthy =
+        let length_weight =
             (input_stroke[length_idx] + ref_stroke[length_idx]) / NUM_POSSIBLE_ENCODED_VALUE_F64;
 
-        score -= MAGIC_PER_STROKE_WEIGHT * NUM_ENCODED_POINTS_F64 * lengthy * angle_similarity;
+        let feature_penalty = if self.layout.features {
+            features::feature_penalty(
+                &input_stroke[length_idx + 1..],
+                &ref_stroke[length_idx + 1..],
+                self.feature_weight,
+            )
+        } else {
+            0.0
+        };
+
+        StrokeTerms {
+            point_penalty,
+            angle_difference,
+            length_weight,
+            angle_penalty: MAGIC_PER_STROKE_WEIGHT
+                * points as f64
+                * length_weight
+                * angle_difference,
+            reversed: false,
+            reversal_penalty: 0.0,
+            dot: false,
+            feature_penalty,
+        }
+    }
+
+    /// Same stroke drawn from end to start: sampled points in reverse order and
+    /// the angle turned by half a circle
+    fn reverse(&self, stroke: &StrokeProcessed) -> StrokeProcessed {
+        let angle_idx = 2 * self.layout.points;
+        let mut reversed: StrokeProcessed = stroke[..angle_idx]
+            .chunks(2)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        reversed.push((stroke[angle_idx] + (NUM_POSSIBLE_ENCODED_VALUE / 2) as f64) % 256.0);
+        reversed.push(stroke[angle_idx + 1]);
+        if self.layout.features {
+            reversed.extend(features::reverse_features(&stroke[angle_idx + 2..]));
+        }
+        reversed
+    }
+
+    /// Similarity score between two stroke sequences (higher = more similar)
+    /// Combines point position differences with angle and length-weighted penalties
+    #[inline]
+    fn similarity(&self, input: &[StrokeProcessed], reference: &[StrokeProcessed]) -> f64 {
+        let mut score = 0.0;
+
+        for i in 0..input.len() {
+            let terms = self.terms(&input[i], &reference[i]);
+            score -= terms.point_penalty;
+            score -= terms.angle_penalty;
+            score -= terms.feature_penalty;
+        }
+
+        score
+    }
+}
+
+/// Per-input scoring tolerances: strokes drawn in the wrong direction and dots
+struct Tolerances {
+    scoring: StrokeScoring,
+    /// Input strokes reversed, when reversal is allowed
+    reversed: Option<Vec<StrokeProcessed>>,
+    reversal_penalty: f64,
+    /// Input strokes small enough to be dots
+    dots: Vec<bool>,
+    dot_max_size: f64,
+    dot_angle_weight: f64,
+}
+
+impl Tolerances {
+    /// None when neither reversal nor dot handling is enabled
+    fn new(
+        input: &[StrokeProcessed],
+        opts: &MatcherOptions,
+        scoring: StrokeScoring,
+    ) -> Option<Self> {
+        if !opts.allow_reversed && !opts.dots.enabled {
+            return None;
+        }
+        Some(Self {
+            scoring,
+            reversed: opts
+                .allow_reversed
+                .then(|| input.iter().map(|s| scoring.reverse(s)).collect()),
+            reversal_penalty: opts.reversal_penalty,
+            dots: {
+                let diagonal = dots::character_diagonal(input);
+                input
+                    .iter()
+                    .map(|s| opts.dots.enabled && dots::is_dot(s, diagonal, opts.dots.max_size))
+                    .collect()
+            },
+            dot_max_size: opts.dots.max_size,
+            dot_angle_weight: opts.dots.angle_weight,
+        })
+    }
+
+    /// Bounding box diagonal of a reference, needed only when the input has dots
+    fn reference_diagonal(&self, reference: &[StrokeProcessed]) -> f64 {
+        if self.dots.contains(&true) {
+            dots::character_diagonal(reference)
+        } else {
+            0.0
+        }
+    }
+
+    /// Compare input stroke `index`: a dot against a template dot by position
+    /// with a relaxed angle, other strokes both ways (if allowed) keeping the
+    /// cheaper comparison
+    #[inline]
+    fn stroke_terms(
+        &self,
+        index: usize,
+        input_stroke: &StrokeProcessed,
+        ref_stroke: &StrokeProcessed,
+        ref_diagonal: f64,
+    ) -> StrokeTerms {
+        let mut forward = self.scoring.terms(input_stroke, ref_stroke);
+        if self.dots[index] && dots::is_dot(ref_stroke, ref_diagonal, self.dot_max_size) {
+            forward.point_penalty = dots::position_penalty(input_stroke, ref_stroke);
+            forward.angle_penalty *= self.dot_angle_weight;
+            forward.dot = true;
+            return forward;
+        }
+        let Some(reversed) = &self.reversed else {
+            return forward;
+        };
+        let mut backward = self.scoring.terms(&reversed[index], ref_stroke);
+        backward.reversed = true;
+        backward.reversal_penalty = self.reversal_penalty;
+        if backward.penalty() < forward.penalty() {
+            backward
+        } else {
+            forward
+        }
+    }
+
+    /// `score_similarity` with the tolerances applied to each stroke
+    #[inline]
+    fn score(&self, input: &[StrokeProcessed], reference: &[StrokeProcessed]) -> f64 {
+        let mut score = 0.0;
+        let ref_diagonal = self.reference_diagonal(reference);
+
+        for i in 0..input.len() {
+            let terms = self.stroke_terms(i, &input[i], &reference[i], ref_diagonal);
+            score -= terms.point_penalty;
+            score -= terms.angle_penalty;
+            score -= terms.reversal_penalty;
+            score -= terms.feature_penalty;
+        }
+
+        score
+    }
+}
+
+/// Per-stroke terms of an input against a reference of the same stroke count
+fn compare_strokes(
+    input: &[StrokeProcessed],
+    reference: &[StrokeProcessed],
+    scoring: StrokeScoring,
+    tolerances: Option<&Tolerances>,
+) -> Vec<StrokeTerms> {
+    let ref_diagonal = tolerances.map_or(0.0, |t| t.reference_diagonal(reference));
+    input
+        .iter()
+        .zip(reference)
+        .enumerate()
+        .map(|(i, (input_stroke, ref_stroke))| match tolerances {
+            Some(tolerances) => tolerances.stroke_terms(i, input_stroke, ref_stroke, ref_diagonal),
+            None => scoring.terms(input_stroke, ref_stroke),
+        })
+        .collect()
+}
+
+/// Indices of input strokes that match the template best in reverse
+fn reversed_strokes(
+    input: &[StrokeProcessed],
+    template: &Template,
+    tolerances: Option<&Tolerances>,
+) -> Vec<usize> {
+    let Some(tolerances) = tolerances.filter(|t| t.reversed.is_some()) else {
+        return Vec::new();
+    };
+    compare_strokes(
+        input,
+        &template.strokes,
+        tolerances.scoring,
+        Some(tolerances),
+    )
+    .iter()
+    .enumerate()
+    .filter(|(_, terms)| terms.reversed)
+    .map(|(i, _)| i)
+    .collect()
+}
+
+/// Size of the canvas the input was drawn on
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Canvas {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// How input coordinates are mapped onto the [0, 255] coordinate space
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Normalization {
+    /// Fit the input's bounding box (size and placement are discarded)
+    #[default]
+    BoundingBox,
+    /// Scale by the canvas size and centre the input: small writing stays small
+    CanvasScale,
+    /// Map the whole canvas: both size and placement are kept
+    CanvasPosition,
+}
+
+impl std::str::FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "bbox" | "bounding-box" => Ok(Normalization::BoundingBox),
+            "canvas-scale" => Ok(Normalization::CanvasScale),
+            "canvas-position" | "canvas" => Ok(Normalization::CanvasPosition),
+            _ => Err(format!("Unknown normalization: {}", s)),
+        }
     }
+}
 
-    score
+impl Normalization {
+    /// Box to project onto the coordinate space; None for bounding-box fitting
+    /// or when the canvas size is unknown or empty
+    fn frame(self, strokes: &[Stroke], canvas: Option<Canvas>) -> Option<AABB> {
+        let canvas = canvas.filter(|c| c.width > 0.0 && c.height > 0.0)?;
+        // The longer canvas side maps to the full range so the aspect ratio is kept
+        let half = canvas.width.max(canvas.height) / 2.0;
+        let center = match self {
+            Normalization::BoundingBox => return None,
+            Normalization::CanvasScale => {
+                let aabb = get_aabb(strokes);
+                [
+                    (aabb[0][0] + aabb[1][0]) / 2.0,
+                    (aabb[0][1] + aabb[1][1]) / 2.0,
+                ]
+            }
+            Normalization::CanvasPosition => [canvas.width / 2.0, canvas.height / 2.0],
+        };
+        Some([
+            [center[0] - half, center[1] - half],
+            [center[0] + half, center[1] + half],
+        ])
+    }
 }
 
 /// Matcher configuration options
+/// Missing fields take their default, so options saved by older versions
+/// still load
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MatcherOptions {
     pub max_ratio: f64,
     pub min_width: f64,
+    /// Score bonus per log-count of past user selections (see `adaptation`)
+    pub adaptation_weight: f64,
+    /// Score bonus per unit of log-frequency (0 disables the frequency prior)
+    pub frequency_weight: f64,
+    /// Score bonus per unit of language-model log probability in `match_with_context`
+    pub context_weight: f64,
+    /// Script the user writes in (see `Matcher::set_script_table`)
+    pub script: Script,
+    /// Whether out-of-script candidates are filtered or demoted
+    pub script_handling: ScriptHandling,
+    /// Score penalty for demoted out-of-script candidates
+    pub script_penalty: f64,
+    /// Report out-of-script candidates as their counterpart in the preferred
+    /// script instead of filtering or demoting them
+    pub map_to_script: bool,
+    /// Also score each stroke drawn end to start and keep the better comparison
+    pub allow_reversed: bool,
+    /// Geometric penalty per stroke matched in reverse
+    pub reversal_penalty: f64,
+    /// Score dots and tiny strokes by position (off by default)
+    pub dots: DotOptions,
+    /// Raw input cleanup before normalization (all steps off by default)
+    pub cleanup: CleanupOptions,
+    /// Timing and pressure handling for `Matcher::match_ink`
+    pub ink: InkOptions,
+    /// Rotation and slant correction after cleanup (off by default)
+    pub deskew: DeskewOptions,
+    /// Coordinate mapping; canvas modes need `MatchQuery::canvas`
+    pub normalization: Normalization,
+    /// Pairwise stroke relation scoring (off by default)
+    pub relations: RelationOptions,
+    /// Writing standard to match, e.g. Japan for KanjiVG forms (see
+    /// `kanjivg::japanese_templates`); its forms replace the base form of the
+    /// same character and forms of other standards are skipped (None matches
+    /// every form)
+    pub standard: Option<Standard>,
+    /// Scale of the shape feature difference relative to the point penalty,
+    /// for databases with stroke features
+    pub feature_weight: f64,
 }
 
 impl Default for MatcherOptions {
@@ -260,6 +736,124 @@ impl Default for MatcherOptions {
         Self {
             max_ratio: 1.0,
             min_width: 8.0,
+            adaptation_weight: 8.0,
+            frequency_weight: 0.0,
+            context_weight: 4.0,
+            script: Script::Both,
+            script_handling: ScriptHandling::Demote,
+            script_penalty: 64.0,
+            map_to_script: false,
+            allow_reversed: false,
+            reversal_penalty: 64.0,
+            dots: DotOptions::default(),
+            cleanup: CleanupOptions::default(),
+            ink: InkOptions::default(),
+            deskew: DeskewOptions::default(),
+            normalization: Normalization::BoundingBox,
+            relations: RelationOptions::default(),
+            standard: None,
+            feature_weight: 0.5,
+        }
+    }
+}
+
+/// Candidate character together with the components of its ranking score
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredCandidate {
+    pub character: Ideograph,
+    /// Writing standard of the best-matching stroke order (None for the base form)
+    pub standard: Option<Standard>,
+    /// Geometric similarity from `score_similarity`, including reversal and
+    /// relation penalties
+    pub geometric: f64,
+    /// Indices of input strokes that matched in reverse (see `MatcherOptions::allow_reversed`)
+    pub reversed: Vec<usize>,
+    /// Bonus learned from the user's past selections
+    pub adaptation: f64,
+    /// Weighted log-frequency prior (0 or negative)
+    pub frequency: f64,
+    /// Weighted language-model log probability given the preceding text (0 or negative)
+    pub context: f64,
+    /// Penalty for being outside the preferred script (0 or negative)
+    pub script: f64,
+    /// Combined score used for ranking (higher = better)
+    pub score: f64,
+}
+
+/// Insert a candidate into a list sorted by descending score, keeping at most
+/// `how_many` entries and only the best-scoring entry per character
+fn insert_candidate(
+    ranked: &mut Vec<ScoredCandidate>,
+    candidate: ScoredCandidate,
+    how_many: usize,
+) {
+    if let Some(existing) = ranked
+        .iter()
+        .position(|c| c.character == candidate.character)
+    {
+        if ranked[existing].score >= candidate.score {
+            return;
+        }
+        ranked.remove(existing);
+    }
+
+    // Insert after candidates with equal score to keep database order on ties
+    let mut f = ranked.len();
+    while f > 0 && candidate.score > ranked[f - 1].score {
+        f -= 1;
+    }
+
+    if how_many > f {
+        ranked.insert(f, candidate);
+        ranked.truncate(how_many);
+    }
+}
+
+/// Per-request matching parameters
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchQuery<'a> {
+    /// Previously committed text, used by the language model reranker
+    pub context: Option<&'a str>,
+    /// Only these characters may be returned (applied before top-k selection)
+    pub filter: Option<&'a CharacterFilter>,
+    /// Canvas the strokes were drawn on, used by `MatcherOptions::normalization`
+    pub canvas: Option<Canvas>,
+}
+
+/// Non-geometric score components of one candidate
+#[derive(Debug, Clone, Copy, Default)]
+struct Priors {
+    adaptation: f64,
+    frequency: f64,
+    context: f64,
+    script: f64,
+}
+
+impl Priors {
+    #[inline]
+    fn total(&self) -> f64 {
+        self.adaptation + self.frequency + self.context + self.script
+    }
+
+    /// Combine with the geometric score into a ranked candidate
+    /// `character` may differ from the template's when mapped to the preferred script
+    fn candidate(
+        self,
+        character: &str,
+        template: &Template,
+        geometric: f64,
+        reversed: Vec<usize>,
+    ) -> ScoredCandidate {
+        ScoredCandidate {
+            character: character.to_string(),
+            standard: template.standard,
+            geometric,
+            reversed,
+            adaptation: self.adaptation,
+            frequency: self.frequency,
+            context: self.context,
+            script: self.script,
+            score: geometric + self.total(),
         }
     }
 }
@@ -267,60 +861,244 @@ impl Default for MatcherOptions {
 /// Main matcher for handwriting recognition
 pub struct Matcher {
     params: MatcherOptions,
-    medians: Vec<(Ideograph, Vec<StrokeProcessed>)>,
+    /// Stroke layout of the database, applied to the input as well
+    layout: StrokeLayout,
+    medians: Vec<Template>,
+    /// Stroke relations of each template, computed on first use when
+    /// `MatcherOptions::relations` is enabled
+    template_relations: Vec<OnceLock<Vec<StrokeRelation>>>,
+    /// Standards each character has variants for
+    standards: HashMap<Ideograph, Vec<Standard>>,
+    /// Whether each template is matched under `MatcherOptions::standard`,
+    /// updated when variants are added
+    selected: Vec<bool>,
+    adaptation: UserAdaptation,
+    metadata: MetadataStore,
+    frequency: FrequencyTable,
+    language_model: NgramModel,
+    script_table: ScriptTable,
+    charsets: HashMap<String, CharacterFilter>,
 }
 
 impl Matcher {
     /// Create a new matcher with character database
+    /// Input is sampled with the points per stroke and features of the
+    /// database's first stroke. Panics if other strokes have another layout;
+    /// `with_layout` reports that as an error instead.
     pub fn new(
         medians: Vec<(Ideograph, Vec<StrokeProcessed>)>,
         options: Option<MatcherOptions>,
     ) -> Self {
-        Self {
-            medians,
+        let layout = data::stroke_layout(&medians).unwrap_or_default();
+        Self::with_layout(medians, layout, options).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a matcher for a database with a known stroke layout, such as
+    /// the one in its file header (see `data::load_graphics_json_with_layout`)
+    /// Every stroke must have that layout.
+    pub fn with_layout(
+        medians: Vec<(Ideograph, Vec<StrokeProcessed>)>,
+        layout: StrokeLayout,
+        options: Option<MatcherOptions>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        data::check_stroke_layout(&medians, layout)?;
+        Ok(Self {
+            layout,
+            template_relations: medians.iter().map(|_| OnceLock::new()).collect(),
+            selected: vec![true; medians.len()],
+            medians: medians
+                .into_iter()
+                .map(|(character, strokes)| Template {
+                    character,
+                    standard: None,
+                    strokes,
+                })
+                .collect(),
             params: options.unwrap_or_default(),
+            standards: HashMap::new(),
+            adaptation: UserAdaptation::default(),
+            metadata: MetadataStore::default(),
+            frequency: FrequencyTable::default(),
+            language_model: NgramModel::default(),
+            script_table: ScriptTable::default(),
+            charsets: HashMap::new(),
+        })
+    }
+
+    /// Number of sampled points per stroke used by the database
+    pub fn points_per_stroke(&self) -> usize {
+        self.layout.points
+    }
+
+    /// Stroke layout used by the database
+    pub fn layout(&self) -> StrokeLayout {
+        self.layout
+    }
+
+    /// Add alternative stroke orders; matching reports each character once,
+    /// scored by its best-matching variant
+    /// Variants must use the database's stroke layout; if any does not,
+    /// none are added
+    pub fn add_variants(
+        &mut self,
+        variants: Vec<Template>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for variant in &variants {
+            data::check_strokes(&variant.character, &variant.strokes, self.layout)?;
+        }
+        for variant in &variants {
+            if let Some(standard) = variant.standard {
+                let standards = self.standards.entry(variant.character.clone()).or_default();
+                if !standards.contains(&standard) {
+                    standards.push(standard);
+                }
+            }
+        }
+        self.template_relations
+            .extend(variants.iter().map(|_| OnceLock::new()));
+        self.medians.extend(variants);
+        self.selected = self.medians.iter().map(|t| self.selects(t)).collect();
+        Ok(())
+    }
+
+    /// Whether a template is matched under `MatcherOptions::standard`
+    fn selects(&self, template: &Template) -> bool {
+        match (self.params.standard, template.standard) {
+            (None, _) => true,
+            (Some(wanted), Some(standard)) => wanted == standard,
+            (Some(wanted), None) => self
+                .standards
+                .get(&template.character)
+                .is_none_or(|standards| !standards.contains(&wanted)),
         }
     }
 
     /// Preprocess user input strokes
     #[inline]
     pub fn preprocess(&self, strokes: &[Stroke]) -> Vec<StrokeProcessed> {
-        preprocess_strokes(strokes, &self.params)
+        self.preprocess_in_canvas(strokes, None)
+    }
+
+    /// Preprocess user input strokes drawn on `canvas`
+    /// Canvas-based normalization falls back to the bounding box without a canvas
+    pub fn preprocess_in_canvas(
+        &self,
+        strokes: &[Stroke],
+        canvas: Option<Canvas>,
+    ) -> Vec<StrokeProcessed> {
+        let cleaned: Vec<Stroke>;
+        let strokes = if self.params.cleanup.is_enabled() {
+            cleaned = cleanup::clean_strokes(strokes, &self.params.cleanup);
+            &cleaned
+        } else {
+            strokes
+        };
+        let deskewed: Vec<Stroke>;
+        let strokes = if self.params.deskew.is_enabled() {
+            deskewed = deskew::deskew_strokes(strokes, &self.params.deskew);
+            &deskewed
+        } else {
+            strokes
+        };
+        preprocess_strokes(
+            strokes,
+            self.layout,
+            &self.params,
+            &self.adaptation.distortion,
+            canvas,
+        )
     }
 
     /// Match input strokes against database and return top candidates
     pub fn match_strokes(&self, strokes: &[Stroke], how_many_candidates: usize) -> Vec<Ideograph> {
+        self.match_scored(strokes, how_many_candidates)
+            .into_iter()
+            .map(|c| c.character)
+            .collect()
+    }
+
+    /// Match input strokes and return top candidates with their score breakdown
+    pub fn match_scored(
+        &self,
+        strokes: &[Stroke],
+        how_many_candidates: usize,
+    ) -> Vec<ScoredCandidate> {
         if strokes.is_empty() {
             return Vec::new();
         }
 
-        let mut candidates: Vec<Ideograph> = Vec::new();
-        let mut scores: Vec<f64> = Vec::new();
-        let strokes2 = self.preprocess(strokes);
+        self.match_query(strokes, how_many_candidates, &MatchQuery::default())
+    }
 
-        // Compare against all characters in database
-        for candidate in &self.medians {
-            if candidate.1.len() == strokes2.len() {
-                let score = score_similarity(&strokes2, &candidate.1);
-
-                // Insert in sorted order (higher scores first)
-                let mut f = scores.len();
-                while f > 0 && score > scores[f - 1] {
-                    f -= 1;
-                }
+    /// Match input strokes written after `context`, reranking candidates with
+    /// the language model (see `set_language_model`)
+    pub fn match_with_context(
+        &self,
+        strokes: &[Stroke],
+        context: &str,
+        how_many_candidates: usize,
+    ) -> Vec<ScoredCandidate> {
+        let query = MatchQuery {
+            context: Some(context),
+            ..Default::default()
+        };
+        self.match_query(strokes, how_many_candidates, &query)
+    }
 
-                if how_many_candidates > f {
-                    candidates.insert(f, candidate.0.clone());
-                    scores.insert(f, score);
-                    if candidates.len() > how_many_candidates {
-                        candidates.pop();
-                        scores.pop();
-                    }
-                }
-            }
+    /// Match input strokes, returning only characters allowed by `filter`
+    pub fn match_filtered(
+        &self,
+        strokes: &[Stroke],
+        filter: &CharacterFilter,
+        how_many_candidates: usize,
+    ) -> Vec<ScoredCandidate> {
+        let query = MatchQuery {
+            filter: Some(filter),
+            ..Default::default()
+        };
+        self.match_query(strokes, how_many_candidates, &query)
+    }
+
+    /// Match input strokes with per-request parameters
+    pub fn match_query(
+        &self,
+        strokes: &[Stroke],
+        how_many_candidates: usize,
+        query: &MatchQuery,
+    ) -> Vec<ScoredCandidate> {
+        if strokes.is_empty() {
+            return Vec::new();
         }
 
-        candidates
+        let strokes2 = self.preprocess_in_canvas(strokes, query.canvas);
+        self.rank(&strokes2, how_many_candidates, query)
+    }
+
+    /// Match timed stylus input: taps, pauses and pen speed are handled as set
+    /// in `MatcherOptions::ink` before the usual preprocessing
+    pub fn match_ink(
+        &self,
+        strokes: &[InkStroke],
+        how_many_candidates: usize,
+        query: &MatchQuery,
+    ) -> Vec<ScoredCandidate> {
+        let strokes = ink::prepare_strokes(strokes, &self.params.ink);
+        self.match_query(&strokes, how_many_candidates, query)
+    }
+
+    /// Match input strokes and attach dictionary metadata to each candidate
+    pub fn match_enriched(
+        &self,
+        strokes: &[Stroke],
+        how_many_candidates: usize,
+    ) -> Vec<EnrichedCandidate> {
+        self.match_scored(strokes, how_many_candidates)
+            .into_iter()
+            .map(|candidate| EnrichedCandidate {
+                metadata: self.metadata.get(&candidate.character).cloned(),
+                candidate,
+            })
+            .collect()
     }
 
     /// Match preprocessed strokes directly (for testing)
@@ -329,36 +1107,381 @@ impl Matcher {
         strokes_processed: &[StrokeProcessed],
         how_many_candidates: usize,
     ) -> Vec<Ideograph> {
+        self.match_preprocessed_scored(strokes_processed, how_many_candidates)
+            .into_iter()
+            .map(|c| c.character)
+            .collect()
+    }
+
+    /// Match preprocessed strokes and return candidates with their score breakdown
+    pub fn match_preprocessed_scored(
+        &self,
+        strokes_processed: &[StrokeProcessed],
+        how_many_candidates: usize,
+    ) -> Vec<ScoredCandidate> {
         if strokes_processed.is_empty() {
             return Vec::new();
         }
 
-        let mut candidates: Vec<Ideograph> = Vec::new();
-        let mut scores: Vec<f64> = Vec::new();
+        self.rank(
+            strokes_processed,
+            how_many_candidates,
+            &MatchQuery::default(),
+        )
+    }
+
+    /// Score every template with the input's stroke count and keep the best
+    /// candidates, one per character
+    fn rank(
+        &self,
+        strokes_processed: &[StrokeProcessed],
+        how_many_candidates: usize,
+        query: &MatchQuery,
+    ) -> Vec<ScoredCandidate> {
+        let mut ranked: Vec<ScoredCandidate> = Vec::new();
+        let mut best_geometric: Option<(usize, f64)> = None;
+        let context = query.context.map(|c| self.language_model.scorer(c));
+        let scoring = self.scoring();
+        let tolerances = self.tolerances(strokes_processed);
+        let relations = self.relations(strokes_processed);
 
         // Compare against all characters in database
-        for candidate in &self.medians {
-            if candidate.1.len() == strokes_processed.len() {
-                let score = score_similarity(strokes_processed, &candidate.1);
-
-                // Insert in sorted order (higher scores first)
-                let mut f = scores.len();
-                while f > 0 && score > scores[f - 1] {
-                    f -= 1;
-                }
+        for (index, candidate) in self.medians.iter().enumerate() {
+            if candidate.strokes.len() != strokes_processed.len() || !self.selected[index] {
+                continue;
+            }
+
+            let mut geometric = match &tolerances {
+                Some(tolerances) => tolerances.score(strokes_processed, &candidate.strokes),
+                None => scoring.similarity(strokes_processed, &candidate.strokes),
+            };
+            if let Some(relations) = &relations {
+                geometric -= self.relation_penalty(relations, index);
+            }
+
+            let Some((character, script)) = self.resolve_script(&candidate.character) else {
+                continue;
+            };
+            if query.filter.is_some_and(|f| !f.allows(character)) {
+                continue;
+            }
+            // Only characters that can be reported drive the confusion lookup
+            if best_geometric.is_none_or(|(_, best)| geometric > best) {
+                best_geometric = Some((index, geometric));
+            }
+            let priors = self.priors(character, script, context.as_ref());
+            let score = geometric + priors.total();
+
+            // Skip the allocation when the candidate cannot enter the list
+            if ranked.len() >= how_many_candidates
+                && ranked.last().is_none_or(|last| score <= last.score)
+            {
+                continue;
+            }
+
+            let reversed = reversed_strokes(strokes_processed, candidate, tolerances.as_ref());
+            insert_candidate(
+                &mut ranked,
+                priors.candidate(character, candidate, geometric, reversed),
+                how_many_candidates,
+            );
+        }
 
-                if how_many_candidates > f {
-                    candidates.insert(f, candidate.0.clone());
-                    scores.insert(f, score);
-                    if candidates.len() > how_many_candidates {
-                        candidates.pop();
-                        scores.pop();
+        // Promote characters the user chose in the past over the geometric winner
+        if let Some((best, _)) = best_geometric {
+            let weight = self.params.adaptation_weight;
+            for (chosen, bonus) in self
+                .adaptation
+                .confusion_boosts(&self.medians[best].character, weight)
+            {
+                for (index, candidate) in self.medians.iter().enumerate() {
+                    if candidate.character != chosen
+                        || candidate.strokes.len() != strokes_processed.len()
+                        || !self.selected[index]
+                    {
+                        continue;
                     }
+                    let Some((character, script)) = self.resolve_script(chosen) else {
+                        continue;
+                    };
+                    if query.filter.is_some_and(|f| !f.allows(character)) {
+                        continue;
+                    }
+                    let mut geometric = match &tolerances {
+                        Some(tolerances) => tolerances.score(strokes_processed, &candidate.strokes),
+                        None => scoring.similarity(strokes_processed, &candidate.strokes),
+                    };
+                    if let Some(relations) = &relations {
+                        geometric -= self.relation_penalty(relations, index);
+                    }
+                    let reversed =
+                        reversed_strokes(strokes_processed, candidate, tolerances.as_ref());
+                    let mut priors = self.priors(character, script, context.as_ref());
+                    priors.adaptation += bonus;
+                    insert_candidate(
+                        &mut ranked,
+                        priors.candidate(character, candidate, geometric, reversed),
+                        how_many_candidates,
+                    );
                 }
             }
         }
 
-        candidates
+        ranked
+    }
+
+    /// Scoring tolerances for `MatcherOptions::allow_reversed` and `MatcherOptions::dots`
+    fn tolerances(&self, strokes_processed: &[StrokeProcessed]) -> Option<Tolerances> {
+        Tolerances::new(strokes_processed, &self.params, self.scoring())
+    }
+
+    /// Stroke comparison for the database layout
+    fn scoring(&self) -> StrokeScoring {
+        StrokeScoring {
+            layout: self.layout,
+            feature_weight: self.params.feature_weight,
+        }
+    }
+
+    /// Input stroke relations when `MatcherOptions::relations` is enabled
+    fn relations(&self, strokes_processed: &[StrokeProcessed]) -> Option<Vec<StrokeRelation>> {
+        let pairs = self.params.relations.pairs;
+        (pairs != RelationPairs::Off).then(|| relations::stroke_relations(strokes_processed, pairs))
+    }
+
+    /// Penalty for the template's stroke relations differing from the input's
+    fn relation_penalty(&self, input: &[StrokeRelation], index: usize) -> f64 {
+        let opts = &self.params.relations;
+        let reference = self.template_relations[index]
+            .get_or_init(|| relations::stroke_relations(&self.medians[index].strokes, opts.pairs));
+        relations::relation_penalty(input, reference, opts)
+    }
+
+    /// Apply the script preference to a template's character
+    /// Returns the character to report and its script penalty, or None if filtered out
+    #[inline]
+    fn resolve_script<'a>(&'a self, character: &'a str) -> Option<(&'a str, f64)> {
+        let script = self.params.script;
+        if script == Script::Both || self.script_table.in_script(character, script) {
+            return Some((character, 0.0));
+        }
+        if self.params.map_to_script
+            && let Some(counterpart) = self.script_table.counterpart(character, script)
+        {
+            return Some((counterpart, 0.0));
+        }
+        match self.params.script_handling {
+            ScriptHandling::Filter => None,
+            ScriptHandling::Demote => Some((character, -self.params.script_penalty)),
+        }
+    }
+
+    /// Non-geometric score components for a candidate character
+    #[inline]
+    fn priors(&self, character: &str, script: f64, context: Option<&ContextScorer>) -> Priors {
+        let context = match context {
+            Some(scorer) if self.params.context_weight != 0.0 => {
+                self.params.context_weight * scorer.log_probability(character)
+            }
+            _ => 0.0,
+        };
+        Priors {
+            adaptation: self
+                .adaptation
+                .boost(character, self.params.adaptation_weight),
+            frequency: self.frequency_prior(character),
+            context,
+            script,
+        }
+    }
+
+    /// Weighted frequency prior for a candidate character
+    #[inline]
+    fn frequency_prior(&self, character: &str) -> f64 {
+        if self.params.frequency_weight == 0.0 {
+            return 0.0;
+        }
+        self.params.frequency_weight * self.frequency.log_prior(character)
+    }
+
+    /// Break down the geometric score of `candidate` for the input, stroke by stroke
+    /// Returns None if the candidate has no template with the input's stroke count
+    pub fn explain(&self, strokes: &[Stroke], candidate: &str) -> Option<Explanation> {
+        if strokes.is_empty() {
+            return None;
+        }
+        self.explain_preprocessed(&self.preprocess(strokes), candidate)
+    }
+
+    /// Break down the geometric score of `candidate` for preprocessed input
+    /// With several stroke order variants, the best-scoring one is explained
+    pub fn explain_preprocessed(
+        &self,
+        strokes_processed: &[StrokeProcessed],
+        candidate: &str,
+    ) -> Option<Explanation> {
+        let tolerances = self.tolerances(strokes_processed);
+        let relations = self.relations(strokes_processed);
+        self.medians
+            .iter()
+            .enumerate()
+            .filter(|(index, t)| {
+                t.character == candidate
+                    && t.strokes.len() == strokes_processed.len()
+                    && self.selected[*index]
+            })
+            .map(|(index, t)| {
+                let terms = compare_strokes(
+                    strokes_processed,
+                    &t.strokes,
+                    self.scoring(),
+                    tolerances.as_ref(),
+                );
+                let explanation = Explanation::new(&t.character, t.standard, terms);
+                match &relations {
+                    Some(relations) => {
+                        explanation.with_relation_penalty(self.relation_penalty(relations, index))
+                    }
+                    None => explanation,
+                }
+            })
+            .max_by(|a, b| a.geometric.total_cmp(&b.geometric))
+    }
+
+    /// Stroke types of a database character, from its base stroke order
+    /// (or its first variant if the base form is missing)
+    pub fn stroke_types(&self, character: &str) -> Option<Vec<StrokeType>> {
+        let mut templates = self.medians.iter().filter(|t| t.character == character);
+        let first = templates.next()?;
+        let template = if first.standard.is_none() {
+            first
+        } else {
+            templates.find(|t| t.standard.is_none()).unwrap_or(first)
+        };
+        Some(stroke_type::stroke_types(&template.strokes))
+    }
+
+    /// Stroke types of handwritten input, classified after preprocessing
+    pub fn classify_input(&self, strokes: &[Stroke]) -> Vec<StrokeType> {
+        if strokes.is_empty() {
+            return Vec::new();
+        }
+        stroke_type::stroke_types(&self.preprocess(strokes))
+    }
+
+    /// Index of the database's stroke code sequences for 五笔画 search
+    pub fn stroke_code_index(&self) -> stroke_search::StrokeCodeIndex<'_> {
+        stroke_search::StrokeCodeIndex::new(self)
+    }
+
+    /// Recognize several characters written side by side in one box
+    pub fn match_line(&self, strokes: &[Stroke], options: &line::LineOptions) -> line::LineResult {
+        line::recognize_line(self, strokes, options)
+    }
+
+    /// Segmenter for characters written on top of each other in one box
+    pub fn overlap_segmenter(
+        &self,
+        options: overlap::OverlapOptions,
+    ) -> overlap::OverlapSegmenter<'_> {
+        overlap::OverlapSegmenter::new(self, options)
+    }
+
+    /// Create a stroke-by-stroke grader for a target character
+    pub fn grader(&self, character: &str) -> Option<grader::StrokeGrader<'_>> {
+        grader::StrokeGrader::new(self, character, grader::GradingOptions::default())
+    }
+
+    /// Attach a character frequency table for the frequency prior
+    /// (enabled by a non-zero `MatcherOptions::frequency_weight`)
+    pub fn set_frequency_table(&mut self, frequency: FrequencyTable) {
+        self.frequency = frequency;
+    }
+
+    /// Attach a character n-gram model for `match_with_context`
+    pub fn set_language_model(&mut self, model: NgramModel) {
+        self.language_model = model;
+    }
+
+    /// Attach the Simplified/Traditional mapping used by `MatcherOptions::script`
+    pub fn set_script_table(&mut self, table: ScriptTable) {
+        self.script_table = table;
+    }
+
+    /// Register a named character set that requests can select as a filter
+    pub fn add_charset(&mut self, name: &str, filter: CharacterFilter) {
+        self.charsets.insert(name.to_string(), filter);
+    }
+
+    /// Look up a character set registered with `add_charset`
+    pub fn charset(&self, name: &str) -> Option<&CharacterFilter> {
+        self.charsets.get(name)
+    }
+
+    /// Character metadata used by `match_enriched`
+    pub fn metadata(&self) -> &MetadataStore {
+        &self.metadata
+    }
+
+    /// Attach a metadata store, e.g. one loaded with `metadata::load_dictionary`
+    pub fn set_metadata(&mut self, metadata: MetadataStore) {
+        self.metadata = metadata;
+    }
+
+    /// Current per-user adaptation state
+    pub fn adaptation(&self) -> &UserAdaptation {
+        &self.adaptation
+    }
+
+    /// Replace the adaptation state, e.g. with one loaded from disk
+    pub fn set_adaptation(&mut self, adaptation: UserAdaptation) {
+        self.adaptation = adaptation;
+    }
+
+    /// Discard all learned adaptation
+    pub fn reset_adaptation(&mut self) {
+        self.adaptation.reset();
+    }
+
+    /// Learn from the user picking `selected` as the intended character for `strokes`
+    /// Updates selection counts, the confusion table and the distortion estimate
+    pub fn record_selection(&mut self, strokes: &[Stroke], selected: &str) {
+        if strokes.is_empty() {
+            return;
+        }
+
+        let corrected = self.preprocess(strokes);
+        let top = self
+            .rank(&corrected, 1, &MatchQuery::default())
+            .into_iter()
+            .next();
+
+        // Distortion is estimated from the uncorrected input
+        let raw = preprocess_strokes(
+            strokes,
+            self.layout,
+            &self.params,
+            &Distortion::default(),
+            None,
+        );
+        let reference = self
+            .medians
+            .iter()
+            .filter(|t| t.character == selected && t.strokes.len() == raw.len())
+            .max_by(|a, b| {
+                let scoring = self.scoring();
+                scoring
+                    .similarity(&raw, &a.strokes)
+                    .total_cmp(&scoring.similarity(&raw, &b.strokes))
+            })
+            .map(|t| t.strokes.as_slice());
+
+        self.adaptation.record(
+            selected,
+            top.as_ref().map(|c| c.character.as_str()),
+            &raw,
+            reference,
+        );
     }
 }
 
@@ -387,4 +1510,355 @@ mod tests {
         assert_eq!(aabb[0], [0.0, 0.0]);
         assert_eq!(aabb[1], [15.0, 20.0]);
     }
+
+    #[test]
+    fn test_record_selection_promotes_choice() {
+        let data = vec![
+            (
+                "一".to_string(),
+                vec![processed_stroke([0.0, 128.0], [255.0, 128.0])],
+            ),
+            (
+                "㇐".to_string(),
+                vec![processed_stroke([0.0, 126.0], [255.0, 129.0])],
+            ),
+        ];
+        let mut matcher = Matcher::new(data, None);
+        let strokes = vec![vec![[0.0, 0.0], [100.0, 0.0]]];
+        assert_eq!(matcher.match_strokes(&strokes, 2)[0], "一");
+
+        for _ in 0..3 {
+            matcher.record_selection(&strokes, "㇐");
+        }
+        let candidates = matcher.match_scored(&strokes, 2);
+        assert_eq!(candidates[0].character, "㇐");
+        assert!(candidates[0].adaptation > 0.0);
+
+        matcher.reset_adaptation();
+        assert_eq!(matcher.match_strokes(&strokes, 2)[0], "一");
+    }
+
+    #[test]
+    fn test_frequency_prior_reranks_close_pair() {
+        let data = vec![
+            (
+                "內".to_string(),
+                vec![processed_stroke([0.0, 128.0], [255.0, 128.0])],
+            ),
+            (
+                "内".to_string(),
+                vec![processed_stroke([0.0, 124.0], [255.0, 127.0])],
+            ),
+        ];
+        let strokes = vec![vec![[0.0, 0.0], [100.0, 0.0]]];
+        let options = MatcherOptions {
+            frequency_weight: 4.0,
+            ..Default::default()
+        };
+        let mut matcher = Matcher::new(data, Some(options));
+        assert_eq!(matcher.match_strokes(&strokes, 2)[0], "內");
+
+        matcher.set_frequency_table(FrequencyTable::from_counts([
+            ("内".to_string(), 1200.0),
+            ("內".to_string(), 15.0),
+        ]));
+        let candidates = matcher.match_scored(&strokes, 2);
+        assert_eq!(candidates[0].character, "内");
+        assert_eq!(candidates[0].frequency, 0.0);
+        assert!(candidates[1].frequency < 0.0);
+        assert!(candidates[1].geometric > candidates[0].geometric);
+    }
+
+    #[test]
+    fn test_match_with_context() {
+        let data = vec![
+            (
+                "內".to_string(),
+                vec![processed_stroke([0.0, 128.0], [255.0, 128.0])],
+            ),
+            (
+                "内".to_string(),
+                vec![processed_stroke([0.0, 124.0], [255.0, 127.0])],
+            ),
+        ];
+        let strokes = vec![vec![[0.0, 0.0], [100.0, 0.0]]];
+        let mut matcher = Matcher::new(data, None);
+        matcher.set_language_model(NgramModel::from_text("国内\n国内\n境內\n"));
+
+        // Without context the language model is not consulted
+        let candidates = matcher.match_scored(&strokes, 2);
+        assert_eq!(candidates[0].character, "內");
+        assert_eq!(candidates[0].context, 0.0);
+
+        let candidates = matcher.match_with_context(&strokes, "国", 2);
+        assert_eq!(candidates[0].character, "内");
+        assert!(candidates[1].context < 0.0);
+    }
+
+    #[test]
+    fn test_script_preference() {
+        let data = vec![
+            (
+                "內".to_string(),
+                vec![processed_stroke([0.0, 128.0], [255.0, 128.0])],
+            ),
+            (
+                "内".to_string(),
+                vec![processed_stroke([0.0, 120.0], [255.0, 123.0])],
+            ),
+        ];
+        let strokes = vec![vec![[0.0, 0.0], [100.0, 0.0]]];
+        let mut table = ScriptTable::default();
+        table.insert("内", "內");
+
+        let match_with = |options: MatcherOptions| {
+            let mut matcher = Matcher::new(data.clone(), Some(options));
+            matcher.set_script_table(table.clone());
+            matcher.match_scored(&strokes, 2)
+        };
+
+        let demoted = match_with(MatcherOptions {
+            script: Script::Simplified,
+            ..Default::default()
+        });
+        assert_eq!(demoted[0].character, "内");
+        assert_eq!(demoted[1].character, "內");
+        assert!(demoted[1].script < 0.0);
+
+        let filtered = match_with(MatcherOptions {
+            script: Script::Simplified,
+            script_handling: ScriptHandling::Filter,
+            ..Default::default()
+        });
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].character, "内");
+
+        // The traditional shape matches best and is reported as its simplified form once
+        let mapped = match_with(MatcherOptions {
+            script: Script::Simplified,
+            map_to_script: true,
+            ..Default::default()
+        });
+        assert_eq!(mapped.len(), 1);
+        assert_eq!(mapped[0].character, "内");
+        assert_eq!(mapped[0].geometric, demoted[1].geometric);
+    }
+
+    #[test]
+    fn test_filter_applied_before_top_k() {
+        let data = vec![
+            (
+                "一".to_string(),
+                vec![processed_stroke([0.0, 128.0], [255.0, 128.0])],
+            ),
+            (
+                "乀".to_string(),
+                vec![processed_stroke([0.0, 100.0], [255.0, 160.0])],
+            ),
+            (
+                "丨".to_string(),
+                vec![processed_stroke([128.0, 0.0], [128.0, 255.0])],
+            ),
+        ];
+        let matcher = Matcher::new(data, None);
+        let strokes = vec![vec![[0.0, 0.0], [100.0, 0.0]]];
+        assert_eq!(matcher.match_strokes(&strokes, 2), vec!["一", "乀"]);
+
+        let filter = CharacterFilter::from_chars("乀丨");
+        let candidates = matcher.match_filtered(&strokes, &filter, 2);
+        let characters: Vec<&str> = candidates.iter().map(|c| c.character.as_str()).collect();
+        assert_eq!(characters, vec!["乀", "丨"]);
+
+        // A confusion learned for the filtered-out 一 does not apply
+        let mut matcher = matcher;
+        let mut adaptation = UserAdaptation::default();
+        adaptation
+            .confusions
+            .entry("一".to_string())
+            .or_default()
+            .insert("丨".to_string(), 3);
+        matcher.set_adaptation(adaptation);
+        assert!(matcher.match_scored(&strokes, 3)[2].adaptation > 0.0);
+        let candidates = matcher.match_filtered(&strokes, &filter, 2);
+        assert_eq!(candidates[1].character, "丨");
+        assert_eq!(candidates[1].adaptation, 0.0);
+    }
+
+    #[test]
+    fn test_reversed_stroke_tolerance() {
+        let data = vec![(
+            "十".to_string(),
+            vec![
+                processed_stroke([0.0, 128.0], [255.0, 128.0]),
+                processed_stroke([128.0, 0.0], [128.0, 255.0]),
+            ],
+        )];
+        // Horizontal stroke drawn right to left
+        let strokes = vec![
+            vec![[90.0, 50.0], [10.0, 50.0]],
+            vec![[50.0, 10.0], [50.0, 90.0]],
+        ];
+
+        let strict = Matcher::new(data.clone(), None).match_scored(&strokes, 1);
+        assert!(strict[0].reversed.is_empty());
+
+        let tolerant = Matcher::new(
+            data,
+            Some(MatcherOptions {
+                allow_reversed: true,
+                ..Default::default()
+            }),
+        );
+        let candidates = tolerant.match_scored(&strokes, 1);
+        assert_eq!(candidates[0].reversed, vec![0]);
+        assert!(candidates[0].geometric > strict[0].geometric);
+        assert!(candidates[0].geometric <= -tolerant.params.reversal_penalty);
+
+        let explanation = tolerant.explain(&strokes, "十").unwrap();
+        assert!(explanation.strokes[0].reversed);
+        assert!(!explanation.strokes[1].reversed);
+        assert_eq!(explanation.geometric, candidates[0].geometric);
+    }
+
+    #[test]
+    fn test_dot_scoring() {
+        let data = vec![(
+            "亠".to_string(),
+            vec![
+                processed_stroke([120.0, 0.0], [135.0, 15.0]),
+                processed_stroke([0.0, 128.0], [255.0, 128.0]),
+            ],
+        )];
+        // Dot tapped with a tiny flick up and to the left
+        let strokes = vec![
+            vec![[51.0, 31.0], [50.0, 30.0]],
+            vec![[0.0, 50.0], [100.0, 50.0]],
+        ];
+
+        let strict = Matcher::new(data.clone(), None).match_scored(&strokes, 1);
+        let options = MatcherOptions {
+            dots: DotOptions {
+                enabled: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let matcher = Matcher::new(data.clone(), Some(options));
+        let candidates = matcher.match_scored(&strokes, 1);
+        assert!(candidates[0].geometric > strict[0].geometric);
+
+        let explanation = matcher.explain(&strokes, "亠").unwrap();
+        assert!(explanation.strokes[0].dot);
+        assert!(!explanation.strokes[1].dot);
+        assert_eq!(explanation.geometric, candidates[0].geometric);
+
+        // Against a long template stroke the tap is scored as usual, even
+        // though its centroid lies on that stroke
+        let mut with_er = data.clone();
+        with_er.push((
+            "二".to_string(),
+            vec![
+                processed_stroke([60.0, 10.0], [195.0, 10.0]),
+                processed_stroke([0.0, 128.0], [255.0, 128.0]),
+            ],
+        ));
+        let strict = Matcher::new(with_er.clone(), None);
+        let matcher = Matcher::new(with_er, Some(options));
+        let explanation = matcher.explain(&strokes, "二").unwrap();
+        assert!(!explanation.strokes[0].dot);
+        assert_eq!(
+            explanation.geometric,
+            strict.explain(&strokes, "二").unwrap().geometric
+        );
+    }
+
+    #[test]
+    fn test_stroke_relations() {
+        let data = vec![(
+            "十".to_string(),
+            vec![
+                processed_stroke([0.0, 128.0], [255.0, 128.0]),
+                processed_stroke([128.0, 0.0], [128.0, 255.0]),
+            ],
+        )];
+        // 丅: the vertical stroke only touches the horizontal one
+        let strokes = vec![
+            vec![[10.0, 10.0], [90.0, 10.0]],
+            vec![[50.0, 10.0], [50.0, 90.0]],
+        ];
+
+        let plain = Matcher::new(data.clone(), None).match_scored(&strokes, 1);
+        let options = MatcherOptions {
+            relations: RelationOptions {
+                pairs: RelationPairs::All,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let matcher = Matcher::new(data, Some(options));
+        assert!(matcher.template_relations[0].get().is_none());
+        let candidates = matcher.match_scored(&strokes, 1);
+        assert!(
+            candidates[0].geometric <= plain[0].geometric - 2.0 * options.relations.contact_penalty
+        );
+        // Template relations are kept for later queries
+        assert_eq!(matcher.template_relations[0].get().map(Vec::len), Some(1));
+
+        let explanation = matcher.explain(&strokes, "十").unwrap();
+        assert!(explanation.relation_penalty > 0.0);
+        assert_eq!(explanation.geometric, candidates[0].geometric);
+    }
+
+    #[test]
+    fn test_canvas_normalization() {
+        let data = vec![
+            (
+                "一".to_string(),
+                vec![processed_stroke([0.0, 128.0], [255.0, 128.0])],
+            ),
+            (
+                "丶".to_string(),
+                vec![processed_stroke([96.0, 128.0], [160.0, 128.0])],
+            ),
+        ];
+        // Short horizontal stroke in a large canvas
+        let strokes = vec![vec![[180.0, 200.0], [220.0, 200.0]]];
+        let query = MatchQuery {
+            canvas: Some(Canvas {
+                width: 400.0,
+                height: 400.0,
+            }),
+            ..Default::default()
+        };
+
+        // The bounding box blows the stroke up to full width
+        let matcher = Matcher::new(data.clone(), None);
+        assert_eq!(matcher.match_query(&strokes, 1, &query)[0].character, "一");
+
+        let matcher = Matcher::new(
+            data,
+            Some(MatcherOptions {
+                normalization: Normalization::CanvasScale,
+                ..Default::default()
+            }),
+        );
+        assert_eq!(matcher.match_query(&strokes, 1, &query)[0].character, "丶");
+        // Without a canvas the bounding box is used
+        assert_eq!(matcher.match_strokes(&strokes, 1), vec!["一"]);
+
+        // 40 of 400 pixels keeps a tenth of the coordinate range
+        let processed = matcher.preprocess_in_canvas(&strokes, query.canvas);
+        assert_eq!(processed[0][0], 115.0);
+        assert_eq!(processed[0][6], 140.0);
+    }
+
+    #[test]
+    fn test_options_from_older_json() {
+        // Options as saved before any of the optional scoring existed
+        let options: MatcherOptions =
+            serde_json::from_str(r#"{"max_ratio": 2.0, "min_width": 8.0}"#).unwrap();
+        assert_eq!(options.max_ratio, 2.0);
+        assert_eq!(options.standard, None);
+        assert_eq!(options.relations, RelationOptions::default());
+    }
 }
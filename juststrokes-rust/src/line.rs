@@ -0,0 +1,308 @@
+use crate::{AABB, Matcher, ScoredCandidate, Stroke, get_aabb};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Options for recognizing several characters written side by side
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LineOptions {
+    /// Most strokes in one character
+    pub max_strokes: usize,
+    /// Horizontal gap, as a fraction of the line height, that always
+    /// separates two characters; the strokes of 川 or 儿 can be a third of
+    /// the height apart
+    pub gap_ratio: f64,
+    /// Horizontal overlap, as a fraction of the line height, tolerated
+    /// between neighbouring characters
+    pub overlap_ratio: f64,
+    /// Cost of each character, so that parts of a character (亻 and 尔) do
+    /// not beat the whole (你)
+    pub character_penalty: f64,
+    /// Weight of the stroke count cost, the negative log share of database
+    /// templates with that many strokes; single strokes score far better
+    /// than whole characters, and few characters have only one or two
+    pub count_weight: f64,
+    /// Characters narrower than this fraction of the line height are penalized
+    pub min_width: f64,
+    /// Penalty for a character of zero width, scaled down linearly to 0 at
+    /// `min_width`
+    pub narrow_penalty: f64,
+    /// Candidates kept per character
+    pub alternates: usize,
+}
+
+impl Default for LineOptions {
+    fn default() -> Self {
+        Self {
+            max_strokes: 30,
+            gap_ratio: 0.6,
+            overlap_ratio: 0.1,
+            character_penalty: 64.0,
+            count_weight: 32.0,
+            min_width: 0.5,
+            narrow_penalty: 256.0,
+            alternates: 5,
+        }
+    }
+}
+
+/// One character of a recognized line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineCharacter {
+    /// Input strokes of the character
+    pub strokes: Range<usize>,
+    /// Best candidates for these strokes, best first
+    pub candidates: Vec<ScoredCandidate>,
+}
+
+/// Best segmentation of a line of handwriting
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct LineResult {
+    /// Best candidate of every character
+    pub text: String,
+    pub characters: Vec<LineCharacter>,
+    /// Total segmentation cost (lower = better)
+    pub cost: f64,
+}
+
+/// Cost of a character by its number of strokes: the negative log share of
+/// database templates with that many strokes, times `weight`
+pub(crate) fn stroke_count_costs(matcher: &Matcher, weight: f64) -> HashMap<usize, f64> {
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for template in &matcher.medians {
+        *counts.entry(template.strokes.len()).or_default() += 1;
+    }
+    let total = matcher.medians.len().max(1) as f64;
+    counts
+        .into_iter()
+        .map(|(n, count)| (n, -(count as f64 / total).ln() * weight))
+        .collect()
+}
+
+/// Stroke boxes and line measures shared by the segmentation search
+struct Layout {
+    boxes: Vec<AABB>,
+    height: f64,
+    /// Boundaries before stroke i that a character cannot span
+    forced: Vec<bool>,
+}
+
+impl Layout {
+    fn new(strokes: &[Stroke], options: &LineOptions) -> Self {
+        let boxes: Vec<AABB> = strokes
+            .iter()
+            .map(|s| get_aabb(std::slice::from_ref(s)))
+            .collect();
+        let line = get_aabb(strokes);
+        let height = line[1][1] - line[0][1];
+
+        let mut forced = vec![false; strokes.len()];
+        let mut right = f64::NEG_INFINITY;
+        for (i, b) in boxes.iter().enumerate() {
+            forced[i] = i > 0 && b[0][0] - right > options.gap_ratio * height;
+            right = right.max(b[1][0]);
+        }
+        Self {
+            boxes,
+            height,
+            forced,
+        }
+    }
+
+    fn extent(&self, strokes: Range<usize>) -> (f64, f64) {
+        self.boxes[strokes]
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(left, right), b| {
+                (left.min(b[0][0]), right.max(b[1][0]))
+            })
+    }
+
+    /// Whether the strokes stay clear of the strokes written just before
+    /// and just after them
+    fn separable(&self, strokes: Range<usize>, options: &LineOptions) -> bool {
+        let tolerance = options.overlap_ratio * self.height;
+        let (left, right) = self.extent(strokes.clone());
+        let before = strokes.start == 0 || left >= self.boxes[strokes.start - 1][1][0] - tolerance;
+        let after =
+            strokes.end == self.boxes.len() || right <= self.boxes[strokes.end][0][0] + tolerance;
+        before && after
+    }
+
+    fn narrow_penalty(&self, strokes: Range<usize>, options: &LineOptions) -> f64 {
+        if self.height <= 0.0 || options.min_width <= 0.0 {
+            return 0.0;
+        }
+        let (left, right) = self.extent(strokes);
+        let shortfall = (options.min_width - (right - left) / self.height).max(0.0);
+        options.narrow_penalty * shortfall / options.min_width
+    }
+}
+
+/// Segment strokes written left to right into characters
+/// Large horizontal gaps always end a character; otherwise every grouping of
+/// consecutive strokes that does not overlap its neighbours is matched, and
+/// the grouping with the lowest total cost (negated matcher scores plus
+/// per-character, stroke count and narrowness penalties) wins. If no grouping is separable,
+/// the overlap test is dropped.
+pub fn recognize_line(matcher: &Matcher, strokes: &[Stroke], options: &LineOptions) -> LineResult {
+    if strokes.is_empty() {
+        return LineResult::default();
+    }
+    let layout = Layout::new(strokes, options);
+    let counts = stroke_count_costs(matcher, options.count_weight);
+    search(matcher, strokes, &layout, &counts, options, true)
+        .or_else(|| search(matcher, strokes, &layout, &counts, options, false))
+        .unwrap_or_default()
+}
+
+fn search(
+    matcher: &Matcher,
+    strokes: &[Stroke],
+    layout: &Layout,
+    counts: &HashMap<usize, f64>,
+    options: &LineOptions,
+    strict: bool,
+) -> Option<LineResult> {
+    let n = strokes.len();
+    // best[i]: cheapest segmentation of the first i strokes and its last character
+    let mut best: Vec<Option<LineCharacter>> = vec![None; n + 1];
+    let mut cost_to = vec![f64::INFINITY; n + 1];
+    cost_to[0] = 0.0;
+
+    for end in 1..=n {
+        for start in (end.saturating_sub(options.max_strokes.max(1))..end).rev() {
+            if cost_to[start].is_finite() && (!strict || layout.separable(start..end, options)) {
+                let candidates = matcher.match_scored(&strokes[start..end], options.alternates);
+                if let Some(top) = candidates.first() {
+                    let cost = cost_to[start] - top.score
+                        + options.character_penalty
+                        + counts.get(&(end - start)).copied().unwrap_or(0.0)
+                        + layout.narrow_penalty(start..end, options);
+                    if cost < cost_to[end] {
+                        cost_to[end] = cost;
+                        best[end] = Some(LineCharacter {
+                            strokes: start..end,
+                            candidates,
+                        });
+                    }
+                }
+            }
+            // A character cannot reach back across a forced boundary
+            if layout.forced[start] {
+                break;
+            }
+        }
+    }
+
+    let cost = cost_to[n];
+    if !cost.is_finite() {
+        return None;
+    }
+    let mut characters = Vec::new();
+    let mut end = n;
+    while end > 0 {
+        let character = best[end].take()?;
+        end = character.strokes.start;
+        characters.push(character);
+    }
+    characters.reverse();
+    let text = characters
+        .iter()
+        .map(|c| c.candidates[0].character.as_str())
+        .collect();
+    Some(LineResult {
+        text,
+        characters,
+        cost,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StrokeLayout;
+    use crate::data::{RawDatabase, build_database};
+
+    fn shifted(strokes: &[Stroke], dx: f64) -> Vec<Stroke> {
+        strokes
+            .iter()
+            .map(|s| s.iter().map(|p| [p[0] + dx, p[1]]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_line_segmentation() {
+        let shi: Vec<Stroke> = vec![
+            vec![[0.0, 50.0], [100.0, 50.0]],
+            vec![[50.0, 0.0], [50.0, 100.0]],
+        ];
+        let ba: Vec<Stroke> = vec![
+            vec![[40.0, 10.0], [0.0, 100.0]],
+            vec![[60.0, 10.0], [100.0, 100.0]],
+        ];
+        let yi: Vec<Stroke> = vec![vec![[0.0, 50.0], [100.0, 50.0]]];
+        let raw: RawDatabase = vec![
+            ("十".to_string(), shi.clone()),
+            ("八".to_string(), ba.clone()),
+            ("一".to_string(), yi.clone()),
+            ("丨".to_string(), vec![vec![[50.0, 0.0], [50.0, 100.0]]]),
+            ("丿".to_string(), vec![vec![[40.0, 10.0], [0.0, 100.0]]]),
+            ("乀".to_string(), vec![vec![[60.0, 10.0], [100.0, 100.0]]]),
+        ];
+        let matcher = Matcher::new(build_database(&raw, StrokeLayout::default()).unwrap(), None);
+
+        // Tight spacing: only the matcher and the overlap test separate them
+        let line = [shi.clone(), shifted(&ba, 110.0), shifted(&yi, 220.0)].concat();
+        let result = recognize_line(&matcher, &line, &LineOptions::default());
+        assert_eq!(result.text, "十八一");
+        assert_eq!(result.characters[1].strokes, 2..4);
+        assert!(result.characters[1].candidates.len() > 1);
+
+        // A wide gap splits even strokes that would form one character
+        let apart = [shi[..1].to_vec(), shifted(&yi, 200.0)].concat();
+        let options = LineOptions::default();
+        assert_eq!(recognize_line(&matcher, &apart, &options).text, "一一");
+        assert_eq!(
+            recognize_line(&matcher, &[], &options),
+            LineResult::default()
+        );
+    }
+
+    #[test]
+    fn test_line_real_database() {
+        let data = crate::data::load_graphics_json("graphics.json").unwrap();
+        let medians: HashMap<_, _> = data.iter().cloned().collect();
+        let matcher = Matcher::new(data, None);
+        // Median strokes of the database at their own proportions, scaled
+        // to a 102-unit character box
+        let written = |character: &str, dx: f64| -> Vec<Stroke> {
+            medians[character]
+                .iter()
+                .map(|s| {
+                    let points = crate::grader::sampled_points(s);
+                    points
+                        .iter()
+                        .map(|p| [p[0] * 0.4 + dx, p[1] * 0.4])
+                        .collect()
+                })
+                .collect()
+        };
+        let options = LineOptions::default();
+        for character in ["川", "儿", "八", "十", "小", "你"] {
+            let result = recognize_line(&matcher, &written(character, 0.0), &options);
+            assert_eq!(result.text, character);
+        }
+        // Spaced apart, and then packed so that the gaps between characters
+        // (25 and 13 units) are no wider than those between the strokes of
+        // 川 (22), 八 (14) and 儿 (24)
+        for pitch in [150.0, 115.0] {
+            let line = [
+                written("川", 0.0),
+                written("八", pitch),
+                written("儿", 2.0 * pitch),
+            ]
+            .concat();
+            assert_eq!(recognize_line(&matcher, &line, &options).text, "川八儿");
+        }
+    }
+}
@@ -1,5 +1,8 @@
-use clap::Parser;
-use juststrokes_rust::{Matcher, csv_data, socket_service};
+use clap::{Parser, Subcommand};
+use juststrokes_rust::{
+    Matcher, MatcherOptions, Normalization, Stroke, charset, csv_data, frequency, kanjivg,
+    language_model, metadata, relations, script, socket_service, stroke_search, svg, variants,
+};
 
 /// JustStrokes - Chinese character handwriting recognition service
 #[derive(Parser)]
@@ -11,14 +14,235 @@ struct Args {
     #[arg(short = 'd', long, default_value = "graphics.csv")]
     data_file: String,
 
+    /// Additional stroke-order variants (CSV: character, standard, strokes)
+    #[arg(long)]
+    variants_file: Option<String>,
+
+    /// KanjiVG stroke paths (kanji/ directory or kanjivg.xml), added as Japanese forms
+    #[arg(long)]
+    kanjivg: Option<String>,
+
+    /// Writing standard whose forms replace the base forms: prc, tw, hk or jp
+    #[arg(long)]
+    standard: Option<variants::Standard>,
+
+    /// Character metadata in makemeahanzi dictionary.txt format
+    #[arg(long)]
+    metadata_file: Option<String>,
+
+    /// Character frequency list (character and count per line)
+    #[arg(long)]
+    frequency_file: Option<String>,
+
+    /// Weight of the log-frequency prior when a frequency list is loaded
+    #[arg(long, default_value_t = 4.0)]
+    frequency_weight: f64,
+
+    /// Character n-gram counts (ngram\tcount) for context-aware reranking
+    #[arg(long)]
+    ngram_file: Option<String>,
+
+    /// Simplified to Traditional mapping (OpenCC STCharacters.txt format)
+    #[arg(long)]
+    variant_table: Option<String>,
+
+    /// Script the user writes in: simplified, traditional or both
+    #[arg(long, default_value = "both")]
+    script: script::Script,
+
+    /// Drop out-of-script candidates instead of demoting them
+    #[arg(long)]
+    filter_script: bool,
+
+    /// Report out-of-script candidates as their counterpart in the chosen script
+    #[arg(long)]
+    map_to_script: bool,
+
+    /// Also match strokes drawn end to start, at a penalty
+    #[arg(long)]
+    allow_reversed: bool,
+
+    /// Geometric penalty per stroke matched in reverse
+    #[arg(long, default_value_t = 64.0)]
+    reversal_penalty: f64,
+
+    /// Score tiny strokes as dots, by position with a relaxed angle
+    #[arg(long)]
+    dots: bool,
+
+    /// Largest dot as a fraction of the character's bounding box diagonal
+    #[arg(long, default_value_t = 0.1)]
+    dot_size: f64,
+
+    /// Input normalization: bbox, canvas-scale or canvas-position
+    #[arg(long, default_value = "bbox")]
+    normalization: Normalization,
+
+    /// Score stroke pair relations: off, consecutive or all
+    #[arg(long, default_value = "off")]
+    relations: relations::RelationPairs,
+
+    /// Named character set for per-request filtering (NAME=PATH, repeatable)
+    #[arg(long = "charset", value_name = "NAME=PATH")]
+    charsets: Vec<String>,
+
     /// Unix socket path for API service
     #[arg(short = 's', long)]
     socket_path: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a per-stroke breakdown of the score of one candidate
+    Explain {
+        /// Candidate character to explain
+        candidate: String,
+
+        /// Input strokes, one argument per stroke: x0,y0,x1,y1,...
+        #[arg(allow_hyphen_values = true, required_unless_present = "reference")]
+        strokes: Vec<String>,
+
+        /// Use the strokes of this database character as input instead
+        #[arg(long, conflicts_with = "strokes")]
+        reference: Option<String>,
+    },
+    /// Look up characters by 五笔画 stroke codes (1-5, '?' or '6' for any stroke, '*' for any run)
+    Strokes {
+        codes: String,
+
+        /// Match only characters with exactly these strokes (default: prefix)
+        #[arg(long)]
+        exact: bool,
+
+        /// Rank by fewest extra strokes instead of frequency
+        #[arg(long)]
+        fewest_strokes: bool,
+
+        /// Rank by how typical of their classes the strokes run instead of frequency
+        #[arg(long, conflicts_with = "fewest_strokes")]
+        plausible: bool,
+
+        /// Strokes allowed to differ from the codes
+        #[arg(long, default_value_t = 0)]
+        mismatches: usize,
+
+        /// Number of characters to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Build a character database from raw medians (makemeahanzi graphics.txt)
+    BuildDb {
+        /// Raw medians file
+        medians: String,
+
+        /// Output database (JSON, or CSV if the name ends in .csv)
+        output: String,
+
+        /// Sampled points per stroke
+        #[arg(long, default_value_t = juststrokes_rust::NUM_ENCODED_POINTS)]
+        points: usize,
+
+        /// Store curvature and corner features with each stroke
+        #[arg(long)]
+        features: bool,
+    },
+    /// Build a character database from a directory of SVG files, one character
+    /// per file named by the character or its hex code point (e.g. 06c38.svg)
+    ImportSvg {
+        /// Directory of SVG files whose <path> elements are the strokes
+        dir: String,
+
+        /// Output database (JSON, or CSV if the name ends in .csv)
+        output: String,
+
+        /// Sampled points per stroke
+        #[arg(long, default_value_t = juststrokes_rust::NUM_ENCODED_POINTS)]
+        points: usize,
+
+        /// Store curvature and corner features with each stroke
+        #[arg(long)]
+        features: bool,
+
+        /// Line segments per Bézier curve
+        #[arg(long, default_value_t = svg::PathOptions::default().curve_segments)]
+        curve_segments: usize,
+
+        /// Accept closed subpaths (stroke outlines give meaningless strokes)
+        #[arg(long)]
+        allow_closed: bool,
+    },
+}
+
+/// Preprocess raw strokes and save them as a JSON or CSV database
+fn write_database(
+    raw: &juststrokes_rust::data::RawDatabase,
+    output: &str,
+    points: usize,
+    features: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let layout = juststrokes_rust::StrokeLayout { points, features };
+    let database = juststrokes_rust::data::build_database(raw, layout)?;
+    if output.ends_with(".csv") {
+        csv_data::save_graphics_csv(output, &database)?;
+    } else {
+        juststrokes_rust::data::save_graphics_json(output, &database)?;
+    }
+    println!(
+        "Wrote {} characters with {} points per stroke{} to {}",
+        database.len(),
+        points,
+        if features { " and features" } else { "" },
+        output
+    );
+    Ok(())
+}
+
+/// Parse a stroke given as comma-separated coordinates
+fn parse_stroke(text: &str) -> Result<Stroke, Box<dyn std::error::Error>> {
+    let values = text
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.is_empty() || values.len() % 2 != 0 {
+        return Err(format!("Stroke needs x,y coordinate pairs: {}", text).into());
+    }
+    Ok(values.chunks(2).map(|p| [p[0], p[1]]).collect())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    if let Some(Command::BuildDb {
+        medians,
+        output,
+        points,
+        features,
+    }) = &args.command
+    {
+        let raw = juststrokes_rust::data::load_medians(medians)?;
+        return write_database(&raw, output, *points, *features);
+    }
+
+    if let Some(Command::ImportSvg {
+        dir,
+        output,
+        points,
+        features,
+        curve_segments,
+        allow_closed,
+    }) = &args.command
+    {
+        let options = svg::PathOptions {
+            curve_segments: *curve_segments,
+            allow_closed: *allow_closed,
+        };
+        let raw = svg::load_svg_dir(dir, &options)?;
+        return write_database(&raw, output, *points, *features);
+    }
+
     let socket_path = args
         .socket_path
         .unwrap_or_else(socket_service::default_socket_path);
@@ -28,16 +252,147 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Loading character database from {}...", args.data_file);
 
     // Load character database
-    let data = if args.data_file.ends_with(".csv") {
-        csv_data::load_graphics_csv(&args.data_file)?
+    let (data, layout) = if args.data_file.ends_with(".csv") {
+        csv_data::load_graphics_csv_with_layout(&args.data_file)?
     } else {
-        juststrokes_rust::data::load_graphics_json(&args.data_file)?
+        juststrokes_rust::data::load_graphics_json_with_layout(&args.data_file)?
     };
 
     println!("Loaded {} characters", data.len());
 
+    // Input strokes of a reference character, taken before the data moves into the matcher
+    let reference = match &args.command {
+        Some(Command::Explain {
+            reference: Some(character),
+            ..
+        }) => Some(
+            data.iter()
+                .find(|(c, _)| c == character)
+                .map(|(_, strokes)| strokes.clone())
+                .ok_or_else(|| format!("Reference character not in database: {}", character))?,
+        ),
+        _ => None,
+    };
+
     // Create matcher
-    let matcher = Matcher::new(data, None);
+    let mut options = MatcherOptions::default();
+    if args.frequency_file.is_some() {
+        options.frequency_weight = args.frequency_weight;
+    }
+    options.script = args.script;
+    options.map_to_script = args.map_to_script;
+    options.allow_reversed = args.allow_reversed;
+    options.normalization = args.normalization;
+    options.relations.pairs = args.relations;
+    options.reversal_penalty = args.reversal_penalty;
+    options.dots.enabled = args.dots;
+    options.dots.max_size = args.dot_size;
+    options.standard = args.standard;
+    if args.filter_script {
+        options.script_handling = script::ScriptHandling::Filter;
+    }
+    let mut matcher = Matcher::with_layout(data, layout, Some(options))?;
+
+    if let Some(path) = &args.variants_file {
+        let variants = variants::load_variants_csv(path)?;
+        println!("Loaded {} stroke-order variants", variants.len());
+        matcher.add_variants(variants)?;
+    }
+
+    if let Some(path) = &args.kanjivg {
+        let raw = kanjivg::load_kanjivg(path)?;
+        println!("Loaded {} KanjiVG characters", raw.len());
+        matcher.add_variants(kanjivg::japanese_templates(&raw, matcher.layout())?)?;
+    }
+
+    if let Some(path) = &args.metadata_file {
+        let store = metadata::load_dictionary(path)?;
+        println!("Loaded metadata for {} characters", store.len());
+        matcher.set_metadata(store);
+    }
+
+    if let Some(path) = &args.frequency_file {
+        let table = frequency::load_frequency_csv(path)?;
+        println!("Loaded frequencies for {} characters", table.len());
+        matcher.set_frequency_table(table);
+    }
+
+    if let Some(path) = &args.ngram_file {
+        matcher.set_language_model(language_model::load_ngram_counts(path)?);
+        println!("Loaded character n-gram model");
+    }
+
+    if let Some(path) = &args.variant_table {
+        let table = script::load_variant_table(path)?;
+        println!("Loaded {} simplified/traditional mappings", table.len());
+        matcher.set_script_table(table);
+    }
+
+    for spec in &args.charsets {
+        let (name, path) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid charset (expected NAME=PATH): {}", spec))?;
+        let filter = charset::load_charset(path)?;
+        println!("Loaded charset {} with {} characters", name, filter.len());
+        matcher.add_charset(name, filter);
+    }
+
+    if let Some(Command::Explain {
+        candidate, strokes, ..
+    }) = &args.command
+    {
+        let input = match reference {
+            Some(processed) => processed,
+            None => {
+                let strokes = strokes
+                    .iter()
+                    .map(|s| parse_stroke(s))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if strokes.is_empty() {
+                    return Err("explain needs input strokes or --reference".into());
+                }
+                matcher.preprocess(&strokes)
+            }
+        };
+        let explanation = matcher
+            .explain_preprocessed(&input, candidate)
+            .ok_or_else(|| {
+                format!(
+                    "{} has no stroke order variant with {} strokes",
+                    candidate,
+                    input.len()
+                )
+            })?;
+        println!("{}", explanation);
+        return Ok(());
+    }
+
+    if let Some(Command::Strokes {
+        codes,
+        exact,
+        fewest_strokes,
+        plausible,
+        mismatches,
+        limit,
+    }) = &args.command
+    {
+        let query = stroke_search::StrokeQuery::parse(codes, !exact)?;
+        let options = stroke_search::SearchOptions {
+            order: if *fewest_strokes {
+                stroke_search::SearchOrder::FewestStrokes
+            } else if *plausible {
+                stroke_search::SearchOrder::Plausibility
+            } else {
+                stroke_search::SearchOrder::Frequency
+            },
+            max_mismatches: *mismatches,
+            limit: *limit,
+        };
+        for found in matcher.stroke_code_index().search(&query, &options) {
+            println!("{}\t{}", found.character, found.codes);
+        }
+        return Ok(());
+    }
 
     // Start socket service
     println!("Starting Unix socket service at {}", socket_path);
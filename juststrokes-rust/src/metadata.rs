@@ -0,0 +1,137 @@
+use crate::{Ideograph, ScoredCandidate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Etymology entry from makemeahanzi's dictionary
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Etymology {
+    /// "ideographic", "pictographic" or "pictophonetic"
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub hint: Option<String>,
+    #[serde(default)]
+    pub phonetic: Option<String>,
+    #[serde(default)]
+    pub semantic: Option<String>,
+}
+
+/// Dictionary information about a single character
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CharacterMetadata {
+    pub character: Ideograph,
+    #[serde(default)]
+    pub pinyin: Vec<String>,
+    #[serde(default)]
+    pub definition: Option<String>,
+    #[serde(default)]
+    pub radical: Option<String>,
+    /// Ideographic Description Sequence, e.g. "⿰氵每"
+    #[serde(default)]
+    pub decomposition: Option<String>,
+    #[serde(default)]
+    pub etymology: Option<Etymology>,
+    /// Path of each stroke's component in the decomposition, e.g. [1] for the
+    /// second part of "⿰氵每" (None for strokes not assigned to a component)
+    #[serde(default)]
+    pub matches: Vec<Option<Vec<usize>>>,
+}
+
+/// Candidate returned together with its dictionary entry (if known)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnrichedCandidate {
+    pub candidate: ScoredCandidate,
+    pub metadata: Option<CharacterMetadata>,
+}
+
+/// Character metadata keyed by ideograph
+#[derive(Debug, Clone, Default)]
+pub struct MetadataStore {
+    entries: HashMap<Ideograph, CharacterMetadata>,
+}
+
+impl MetadataStore {
+    /// Look up the entry for a character
+    pub fn get(&self, character: &str) -> Option<&CharacterMetadata> {
+        self.entries.get(character)
+    }
+
+    /// Add or replace an entry
+    pub fn insert(&mut self, metadata: CharacterMetadata) {
+        self.entries.insert(metadata.character.clone(), metadata);
+    }
+
+    /// All entries, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &CharacterMetadata> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Load character metadata from makemeahanzi's dictionary.txt
+/// Format: one JSON object per line with character, pinyin, definition,
+/// radical, decomposition and etymology fields (unknown fields are ignored)
+pub fn load_dictionary<P: AsRef<Path>>(
+    path: P,
+) -> Result<MetadataStore, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    let reader = BufReader::new(file);
+    let mut store = MetadataStore::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        store.insert(serde_json::from_str(&line)?);
+    }
+
+    Ok(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_dictionary() {
+        let path = std::env::temp_dir().join("juststrokes_dictionary_test.txt");
+        fs::write(
+            &path,
+            concat!(
+                r#"{"character":"海","definition":"sea, ocean; maritime","pinyin":["hǎi"],"decomposition":"⿰氵每","etymology":{"type":"pictophonetic","phonetic":"每","semantic":"氵","hint":"water"},"radical":"氵","matches":[[0],[0],[0],[1],[1],[1],[1],[1],[1],[1]]}"#,
+                "\n",
+                r#"{"character":"⺀","definition":"ice","pinyin":[],"decomposition":"？","radical":"⺀","matches":[null,null]}"#,
+                "\n"
+            ),
+        )
+        .unwrap();
+
+        let store = load_dictionary(&path).expect("Failed to load dictionary");
+        assert_eq!(store.len(), 2);
+
+        let hai = store.get("海").unwrap();
+        assert_eq!(hai.pinyin, vec!["hǎi"]);
+        assert_eq!(hai.radical.as_deref(), Some("氵"));
+        assert_eq!(hai.decomposition.as_deref(), Some("⿰氵每"));
+        assert_eq!(hai.matches[3], Some(vec![1]));
+        let etymology = hai.etymology.as_ref().unwrap();
+        assert_eq!(etymology.kind, "pictophonetic");
+        assert_eq!(etymology.semantic.as_deref(), Some("氵"));
+
+        assert!(store.get("⺀").unwrap().etymology.is_none());
+        assert!(store.get("水").is_none());
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,433 @@
+use crate::adaptation::Distortion;
+use crate::grader::sampled_points;
+use crate::ink::{self, InkPoint};
+use crate::line::{LineCharacter, LineResult, stroke_count_costs};
+use crate::{Matcher, ScoredCandidate, Stroke, StrokeProcessed, preprocess_strokes};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Options for segmenting characters written on top of each other
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OverlapOptions {
+    /// Most strokes in one character
+    pub max_strokes: usize,
+    /// Cost of each character, so that parts of a character do not beat the whole
+    pub character_penalty: f64,
+    /// Weight of the stroke count cost, the negative log share of database
+    /// templates with that many strokes; a stroke matched alone scores far
+    /// better than inside its character, so 十 would otherwise become 一丨
+    pub count_weight: f64,
+    /// Pen-up time in milliseconds that hints at a character boundary (0 disables)
+    pub pause: f64,
+    /// Cost reduction for a boundary after such a pause
+    pub pause_bonus: f64,
+    /// Consecutive strokes after which the first character of the best
+    /// segmentation must still be the same before it is committed; a boundary
+    /// after a pause commits at once
+    pub confirmations: usize,
+    /// Candidates kept per character
+    pub alternates: usize,
+}
+
+impl Default for OverlapOptions {
+    fn default() -> Self {
+        Self {
+            max_strokes: 30,
+            character_penalty: 64.0,
+            count_weight: 32.0,
+            pause: 500.0,
+            pause_bonus: 64.0,
+            confirmations: 2,
+            alternates: 5,
+        }
+    }
+}
+
+/// Incremental segmenter for a continuous stream of overlapped strokes
+/// Strokes carry no spatial hint of where a character ends, so after every
+/// stroke the pending strokes are segmented by matcher scores, stroke count
+/// plausibility and pauses. The last character may still be unfinished and is
+/// then matched against the first strokes of longer characters. The first
+/// character is committed once it stays first in the best segmentation, and
+/// its strokes leave the search.
+pub struct OverlapSegmenter<'a> {
+    matcher: &'a Matcher,
+    options: OverlapOptions,
+    /// Stroke count cost by number of strokes
+    count_costs: HashMap<usize, f64>,
+    /// Index of the first pending stroke in the whole stream
+    offset: usize,
+    pending: Vec<Stroke>,
+    /// Pen-up time before each pending stroke
+    pauses: Vec<f64>,
+    last_time: Option<f64>,
+    /// Candidates by absolute stroke range and whether the character may be
+    /// unfinished, kept while the strokes are pending
+    cache: HashMap<(usize, usize, bool), Vec<ScoredCandidate>>,
+    /// Matchers over the first strokes of longer templates, by stroke count;
+    /// each holds a preprocessed copy of those strokes
+    prefixes: HashMap<usize, Matcher>,
+    /// First character of the last best segmentation and how often it was first
+    leading: Option<(Range<usize>, usize)>,
+    committed: Vec<LineCharacter>,
+}
+
+impl<'a> OverlapSegmenter<'a> {
+    /// Segmenter over `matcher`'s database
+    /// Unfinished characters are matched against prefix templates, which are
+    /// preprocessed on first use for each stroke count and kept by the
+    /// segmenter. Once all stroke counts are in use they hold about six times
+    /// the strokes of a full database, so keep one segmenter per writing
+    /// session instead of one per character.
+    pub fn new(matcher: &'a Matcher, options: OverlapOptions) -> Self {
+        Self {
+            matcher,
+            options,
+            count_costs: stroke_count_costs(matcher, options.count_weight),
+            offset: 0,
+            pending: Vec::new(),
+            pauses: Vec::new(),
+            last_time: None,
+            cache: HashMap::new(),
+            prefixes: HashMap::new(),
+            leading: None,
+            committed: Vec::new(),
+        }
+    }
+
+    /// Add a timed stroke; returns the characters committed by it
+    /// Untimed strokes (see `ink::from_stroke`) give no pause hints
+    pub fn push_stroke(&mut self, stroke: &[InkPoint]) -> Vec<LineCharacter> {
+        let (Some(first), Some(last)) = (stroke.first(), stroke.last()) else {
+            return Vec::new();
+        };
+        let pause = self.last_time.map_or(0.0, |t| (first.t - t).max(0.0));
+        self.last_time = Some(last.t);
+        self.pending.push(ink::to_stroke(stroke));
+        self.pauses.push(pause);
+
+        let Some((best, _)) = self.segment(true) else {
+            return Vec::new();
+        };
+        if best.len() < 2 {
+            self.leading = None;
+            return Vec::new();
+        }
+        let first = best[0].strokes.clone();
+        let seen = match &self.leading {
+            Some((range, seen)) if *range == first => seen + 1,
+            _ => 1,
+        };
+        let after_pause =
+            self.pauses[first.end - self.offset] >= self.options.pause && self.options.pause > 0.0;
+        if seen >= self.options.confirmations || after_pause {
+            self.commit(best.into_iter().take(1))
+        } else {
+            self.leading = Some((first, seen));
+            Vec::new()
+        }
+    }
+
+    /// Commit every pending stroke, e.g. when the user stops writing
+    /// If the strokes do not end with a complete character, the unfinished one
+    /// is committed as its most likely completion
+    pub fn finish(&mut self) -> Vec<LineCharacter> {
+        match self.segment(false).or_else(|| self.segment(true)) {
+            Some((best, _)) => self.commit(best),
+            None => Vec::new(),
+        }
+    }
+
+    /// Current best segmentation of the strokes not yet committed; its last
+    /// character may be unfinished
+    pub fn tentative(&mut self) -> LineResult {
+        let (characters, cost) = self.segment(true).unwrap_or_default();
+        LineResult {
+            text: text(&characters),
+            characters,
+            cost,
+        }
+    }
+
+    /// Characters committed so far, with stroke ranges in the whole stream
+    pub fn committed(&self) -> &[LineCharacter] {
+        &self.committed
+    }
+
+    /// Best candidates of the committed characters
+    pub fn text(&self) -> String {
+        text(&self.committed)
+    }
+
+    fn commit(
+        &mut self,
+        characters: impl IntoIterator<Item = LineCharacter>,
+    ) -> Vec<LineCharacter> {
+        let characters: Vec<LineCharacter> = characters.into_iter().collect();
+        if let Some(last) = characters.last() {
+            let drop = last.strokes.end - self.offset;
+            self.pending.drain(..drop);
+            self.pauses.drain(..drop);
+            self.offset = last.strokes.end;
+            let offset = self.offset;
+            self.cache.retain(|(start, _, _), _| *start >= offset);
+        }
+        self.leading = None;
+        self.committed.extend(characters.iter().cloned());
+        characters
+    }
+
+    fn candidates(&mut self, strokes: Range<usize>, unfinished: bool) -> &[ScoredCandidate] {
+        let key = (strokes.start, strokes.end, unfinished);
+        if !self.cache.contains_key(&key) {
+            let input = &self.pending[strokes.start - self.offset..strokes.end - self.offset];
+            let found = if unfinished {
+                let source = self.matcher;
+                self.prefixes
+                    .entry(input.len())
+                    .or_insert_with(|| prefix_matcher(source, input.len()))
+                    .match_scored(input, self.options.alternates)
+            } else {
+                self.matcher.match_scored(input, self.options.alternates)
+            };
+            self.cache.insert(key, found);
+        }
+        &self.cache[&key]
+    }
+
+    /// Cost of a character, including the pause bonus for its start
+    /// An unfinished character has no stroke count cost yet
+    fn cost(&self, character: &LineCharacter, unfinished: bool) -> f64 {
+        let strokes = &character.strokes;
+        let count = if unfinished {
+            0.0
+        } else {
+            self.count_costs.get(&strokes.len()).copied().unwrap_or(0.0)
+        };
+        let paused = strokes.start > self.offset
+            && self.options.pause > 0.0
+            && self.pauses[strokes.start - self.offset] >= self.options.pause;
+        let bonus = if paused {
+            self.options.pause_bonus
+        } else {
+            0.0
+        };
+        -character.candidates[0].score + self.options.character_penalty + count - bonus
+    }
+
+    /// Lowest-cost segmentation of the pending strokes and its cost
+    fn segment(&mut self, unfinished: bool) -> Option<(Vec<LineCharacter>, f64)> {
+        let n = self.pending.len();
+        if n == 0 {
+            return None;
+        }
+        let mut best: Vec<Option<LineCharacter>> = vec![None; n + 1];
+        let mut cost_to = vec![f64::INFINITY; n + 1];
+        cost_to[0] = 0.0;
+        for end in 1..=n {
+            for start in end.saturating_sub(self.options.max_strokes.max(1))..end {
+                if !cost_to[start].is_finite() {
+                    continue;
+                }
+                let strokes = self.offset + start..self.offset + end;
+                for open in [false, unfinished && end == n] {
+                    let candidates = self.candidates(strokes.clone(), open).to_vec();
+                    if candidates.is_empty() {
+                        continue;
+                    }
+                    let character = LineCharacter {
+                        strokes: strokes.clone(),
+                        candidates,
+                    };
+                    let cost = cost_to[start] + self.cost(&character, open);
+                    if cost < cost_to[end] {
+                        cost_to[end] = cost;
+                        best[end] = Some(character);
+                    }
+                }
+            }
+        }
+
+        let mut characters = Vec::new();
+        let mut end = n;
+        while end > 0 {
+            let character = best[end].take()?;
+            end = character.strokes.start - self.offset;
+            characters.push(character);
+        }
+        characters.reverse();
+        Some((characters, cost_to[n]))
+    }
+}
+
+/// Matcher over the first `strokes` strokes of every longer template,
+/// re-normalized as if drawn alone
+fn prefix_matcher(source: &Matcher, strokes: usize) -> Matcher {
+    let templates: Vec<(String, Vec<StrokeProcessed>)> = source
+        .medians
+        .iter()
+        .filter(|t| t.strokes.len() > strokes)
+        .map(|t| {
+            let raw: Vec<Stroke> = t.strokes[..strokes].iter().map(sampled_points).collect();
+            let processed = preprocess_strokes(
+                &raw,
+                source.layout,
+                &source.params,
+                &Distortion::default(),
+                None,
+            );
+            (t.character.clone(), processed)
+        })
+        .collect();
+    Matcher::with_layout(templates, source.layout, Some(source.params))
+        .expect("prefix templates keep the source layout")
+}
+
+fn text(characters: &[LineCharacter]) -> String {
+    characters
+        .iter()
+        .map(|c| c.candidates[0].character.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StrokeLayout;
+    use crate::data::{RawDatabase, build_database};
+    use crate::ink::InkStroke;
+
+    fn matcher() -> Matcher {
+        let stroke = |a: [f64; 2], b: [f64; 2]| vec![a, b];
+        let heng = stroke([0.0, 50.0], [100.0, 50.0]);
+        let shu = stroke([50.0, 0.0], [50.0, 100.0]);
+        let pie = stroke([40.0, 10.0], [0.0, 100.0]);
+        let na = stroke([60.0, 10.0], [100.0, 100.0]);
+        let raw: RawDatabase = vec![
+            ("十".to_string(), vec![heng.clone(), shu.clone()]),
+            ("八".to_string(), vec![pie.clone(), na.clone()]),
+            ("一".to_string(), vec![heng]),
+            ("丨".to_string(), vec![shu]),
+            ("丿".to_string(), vec![pie]),
+            ("乀".to_string(), vec![na]),
+        ];
+        Matcher::new(build_database(&raw, StrokeLayout::default()).unwrap(), None)
+    }
+
+    /// 十 and 八 written in the same box, 100 ms per stroke and 50 ms between
+    /// strokes, with an extra pause before stroke `pause_before`
+    fn strokes(pause_before: usize, pause: f64) -> Vec<InkStroke> {
+        let raw: [Stroke; 4] = [
+            vec![[0.0, 50.0], [100.0, 50.0]],
+            vec![[50.0, 0.0], [50.0, 100.0]],
+            vec![[40.0, 10.0], [0.0, 100.0]],
+            vec![[60.0, 10.0], [100.0, 100.0]],
+        ];
+        let mut start = 0.0;
+        raw.iter()
+            .enumerate()
+            .map(|(i, s)| {
+                if i == pause_before {
+                    start += pause;
+                }
+                let ink = vec![
+                    InkPoint {
+                        x: s[0][0],
+                        y: s[0][1],
+                        t: start,
+                        pressure: 1.0,
+                    },
+                    InkPoint {
+                        x: s[1][0],
+                        y: s[1][1],
+                        t: start + 100.0,
+                        pressure: 1.0,
+                    },
+                ];
+                start += 150.0;
+                ink
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_overlapped_segmentation() {
+        let matcher = matcher();
+        let mut segmenter = OverlapSegmenter::new(&matcher, OverlapOptions::default());
+        let mut committed = Vec::new();
+        for stroke in strokes(0, 0.0) {
+            committed.push(segmenter.push_stroke(&stroke).len());
+        }
+        // 十 is committed once it stays first for a second stroke
+        assert_eq!(committed, [0, 0, 0, 1]);
+        assert_eq!(segmenter.text(), "十");
+        assert_eq!(segmenter.tentative().text, "八");
+
+        let rest = segmenter.finish();
+        assert_eq!(rest[0].strokes, 2..4);
+        assert_eq!(segmenter.text(), "十八");
+        assert!(segmenter.finish().is_empty());
+    }
+
+    #[test]
+    fn test_pause_commits_early() {
+        let matcher = matcher();
+        let mut segmenter = OverlapSegmenter::new(&matcher, OverlapOptions::default());
+        let input = strokes(2, 800.0);
+        assert!(segmenter.push_stroke(&input[0]).is_empty());
+        assert!(segmenter.push_stroke(&input[1]).is_empty());
+        let committed = segmenter.push_stroke(&input[2]);
+        assert_eq!(committed.len(), 1);
+        assert_eq!(committed[0].candidates[0].character, "十");
+    }
+
+    #[test]
+    fn test_overlap_real_database() {
+        let data = crate::data::load_graphics_json("graphics.json").unwrap();
+        let medians: HashMap<_, _> = data.iter().cloned().collect();
+        let matcher = Matcher::new(data, None);
+        let written = |character: &str| -> Vec<Stroke> {
+            medians[character].iter().map(sampled_points).collect()
+        };
+        let straight_shi: Vec<Stroke> = vec![
+            vec![[0.0, 50.0], [100.0, 50.0]],
+            vec![[50.0, 0.0], [50.0, 100.0]],
+        ];
+        // Characters written in one box, 150 ms per stroke and `pause` more
+        // between characters
+        let segment = |characters: &[Vec<Stroke>], pause: f64| -> String {
+            let mut segmenter = OverlapSegmenter::new(&matcher, OverlapOptions::default());
+            let mut start = 0.0;
+            for strokes in characters {
+                for stroke in strokes {
+                    let ink: InkStroke = stroke
+                        .iter()
+                        .enumerate()
+                        .map(|(i, p)| InkPoint {
+                            x: p[0],
+                            y: p[1],
+                            t: start + 30.0 * i as f64,
+                            pressure: 1.0,
+                        })
+                        .collect();
+                    segmenter.push_stroke(&ink);
+                    start += 150.0;
+                }
+                start += pause;
+            }
+            segmenter.finish();
+            segmenter.text()
+        };
+        assert_eq!(segment(std::slice::from_ref(&straight_shi), 0.0), "十");
+        assert_eq!(segment(&[written("十")], 0.0), "十");
+        assert_eq!(segment(&[written("口")], 0.0), "口");
+        for pause in [0.0, 800.0] {
+            assert_eq!(segment(&[written("十"), written("口")], pause), "十口");
+            assert_eq!(
+                segment(&[straight_shi.clone(), written("口")], pause),
+                "十口"
+            );
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{Point, StrokeProcessed, encoded_points};
+use serde::{Deserialize, Serialize};
+
+/// Largest gap, in normalized units, between an end point and another stroke
+/// that still counts as touching (about 3% of the coordinate space)
+const TOUCH_DISTANCE: f64 = 8.0;
+
+/// Which stroke pairs are compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RelationPairs {
+    /// No pairwise comparison
+    #[default]
+    Off,
+    /// Each stroke with the next one
+    Consecutive,
+    /// Every pair of strokes
+    All,
+}
+
+impl std::str::FromStr for RelationPairs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "none" => Ok(RelationPairs::Off),
+            "consecutive" => Ok(RelationPairs::Consecutive),
+            "all" => Ok(RelationPairs::All),
+            _ => Err(format!("Unknown relation pairs: {}", s)),
+        }
+    }
+}
+
+/// Pairwise stroke relation scoring (off by default)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RelationOptions {
+    pub pairs: RelationPairs,
+    /// Penalty per unit of centroid offset difference
+    pub offset_weight: f64,
+    /// Penalty when a pair crosses, touches or stays apart differently
+    pub contact_penalty: f64,
+    /// Penalty when the dominant direction between a pair differs
+    pub direction_penalty: f64,
+}
+
+impl Default for RelationOptions {
+    fn default() -> Self {
+        Self {
+            pairs: RelationPairs::Off,
+            offset_weight: 0.5,
+            contact_penalty: 64.0,
+            direction_penalty: 32.0,
+        }
+    }
+}
+
+/// How two strokes meet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Contact {
+    Apart,
+    /// An end point lies on or near the other stroke
+    Touching,
+    Crossing,
+}
+
+/// Where the second stroke of a pair lies relative to the first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Above,
+    Below,
+    LeftOf,
+    RightOf,
+}
+
+/// Relation of stroke `second` to stroke `first`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StrokeRelation {
+    pub first: usize,
+    pub second: usize,
+    pub contact: Contact,
+    /// Centroid offset of the second stroke from the first
+    pub dx: f64,
+    pub dy: f64,
+}
+
+impl StrokeRelation {
+    /// Dominant direction of the centroid offset (y points down)
+    pub fn direction(&self) -> Direction {
+        if self.dy.abs() >= self.dx.abs() {
+            if self.dy < 0.0 {
+                Direction::Above
+            } else {
+                Direction::Below
+            }
+        } else if self.dx < 0.0 {
+            Direction::LeftOf
+        } else {
+            Direction::RightOf
+        }
+    }
+}
+
+/// Sampled points of a preprocessed stroke as a polyline
+fn polyline(stroke: &StrokeProcessed) -> Vec<Point> {
+    stroke[..2 * encoded_points(stroke)]
+        .chunks(2)
+        .map(|p| [p[0], p[1]])
+        .collect()
+}
+
+fn centroid(points: &[Point]) -> Point {
+    let n = points.len().max(1) as f64;
+    [
+        points.iter().map(|p| p[0]).sum::<f64>() / n,
+        points.iter().map(|p| p[1]).sum::<f64>() / n,
+    ]
+}
+
+fn cross(o: Point, a: Point, b: Point) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Proper intersection of segments ab and cd (touching end points excluded)
+fn segments_cross(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+fn point_segment_distance(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    (p[0] - a[0] - t * dx).hypot(p[1] - a[1] - t * dy)
+}
+
+fn near_polyline(p: Point, line: &[Point]) -> bool {
+    match line {
+        [single] => (p[0] - single[0]).hypot(p[1] - single[1]) <= TOUCH_DISTANCE,
+        _ => line
+            .windows(2)
+            .any(|w| point_segment_distance(p, w[0], w[1]) <= TOUCH_DISTANCE),
+    }
+}
+
+/// Contact between two polylines
+pub fn contact(a: &[Point], b: &[Point]) -> Contact {
+    for s in a.windows(2) {
+        for t in b.windows(2) {
+            if segments_cross(s[0], s[1], t[0], t[1]) {
+                return Contact::Crossing;
+            }
+        }
+    }
+    let ends = |line: &[Point]| [line.first().copied(), line.last().copied()];
+    let touching = ends(a).into_iter().flatten().any(|p| near_polyline(p, b))
+        || ends(b).into_iter().flatten().any(|p| near_polyline(p, a));
+    if touching {
+        Contact::Touching
+    } else {
+        Contact::Apart
+    }
+}
+
+/// Relations between the selected stroke pairs, in a fixed pair order
+pub fn stroke_relations(strokes: &[StrokeProcessed], pairs: RelationPairs) -> Vec<StrokeRelation> {
+    let lines: Vec<Vec<Point>> = strokes.iter().map(polyline).collect();
+    let centroids: Vec<Point> = lines.iter().map(|l| centroid(l)).collect();
+    let relation = |first: usize, second: usize| StrokeRelation {
+        first,
+        second,
+        contact: contact(&lines[first], &lines[second]),
+        dx: centroids[second][0] - centroids[first][0],
+        dy: centroids[second][1] - centroids[first][1],
+    };
+
+    let n = strokes.len();
+    match pairs {
+        RelationPairs::Off => Vec::new(),
+        RelationPairs::Consecutive => (1..n).map(|j| relation(j - 1, j)).collect(),
+        RelationPairs::All => (0..n)
+            .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+            .map(|(i, j)| relation(i, j))
+            .collect(),
+    }
+}
+
+/// Penalty for differing relations between the same stroke pairs
+/// The pair penalties are averaged and scaled by the stroke count, so the total
+/// is comparable to the per-stroke terms whichever pairs are compared
+pub fn relation_penalty(
+    input: &[StrokeRelation],
+    reference: &[StrokeRelation],
+    opts: &RelationOptions,
+) -> f64 {
+    if input.is_empty() || input.len() != reference.len() {
+        return 0.0;
+    }
+    let total: f64 = input
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| {
+            let mut penalty = opts.offset_weight * ((a.dx - b.dx).abs() + (a.dy - b.dy).abs());
+            if a.contact != b.contact {
+                penalty += opts.contact_penalty;
+            }
+            if a.direction() != b.direction() {
+                penalty += opts.direction_penalty;
+            }
+            penalty
+        })
+        .sum();
+    let strokes = input.iter().map(|r| r.second).max().unwrap_or(0) + 1;
+    total / input.len() as f64 * strokes as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processed_stroke;
+
+    #[test]
+    fn test_crossing_and_touching() {
+        let horizontal = processed_stroke([0.0, 0.0], [255.0, 0.0]);
+        let crossing = processed_stroke([128.0, 255.0], [128.0, -100.0]);
+        let touching = processed_stroke([128.0, 0.0], [128.0, 255.0]);
+        let apart = processed_stroke([128.0, 30.0], [128.0, 255.0]);
+
+        let relations = |second: &StrokeProcessed| {
+            stroke_relations(&[horizontal.clone(), second.clone()], RelationPairs::All)
+        };
+        assert_eq!(relations(&crossing)[0].contact, Contact::Crossing);
+        assert_eq!(relations(&touching)[0].contact, Contact::Touching);
+        assert_eq!(relations(&apart)[0].contact, Contact::Apart);
+        assert_eq!(relations(&touching)[0].direction(), Direction::Below);
+
+        let opts = RelationOptions::default();
+        let (a, b) = (relations(&touching), relations(&apart));
+        assert_eq!(relation_penalty(&a, &a, &opts), 0.0);
+        assert!(relation_penalty(&a, &b, &opts) > opts.contact_penalty);
+    }
+}
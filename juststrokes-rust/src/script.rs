@@ -0,0 +1,156 @@
+use crate::Ideograph;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Chinese script the user writes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Script {
+    Simplified,
+    Traditional,
+    /// Accept characters of either script
+    #[default]
+    Both,
+}
+
+impl FromStr for Script {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "simplified" | "s" | "zh-hans" => Ok(Script::Simplified),
+            "traditional" | "t" | "zh-hant" => Ok(Script::Traditional),
+            "both" => Ok(Script::Both),
+            _ => Err(format!("Unknown script: {}", s)),
+        }
+    }
+}
+
+/// What to do with candidates outside the preferred script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ScriptHandling {
+    /// Drop them before top-k selection
+    Filter,
+    /// Keep them with `MatcherOptions::script_penalty` subtracted
+    #[default]
+    Demote,
+}
+
+/// Simplified/Traditional character mapping
+#[derive(Debug, Clone, Default)]
+pub struct ScriptTable {
+    to_traditional: HashMap<Ideograph, Vec<Ideograph>>,
+    to_simplified: HashMap<Ideograph, Vec<Ideograph>>,
+}
+
+impl ScriptTable {
+    /// Record that `simplified` is written `traditional` in Traditional script
+    pub fn insert(&mut self, simplified: &str, traditional: &str) {
+        let forms = self
+            .to_traditional
+            .entry(simplified.to_string())
+            .or_default();
+        if !forms.iter().any(|f| f == traditional) {
+            forms.push(traditional.to_string());
+        }
+        let forms = self
+            .to_simplified
+            .entry(traditional.to_string())
+            .or_default();
+        if !forms.iter().any(|f| f == simplified) {
+            forms.push(simplified.to_string());
+        }
+    }
+
+    /// True when the character is used in the given script
+    /// Characters absent from the table are shared by both scripts
+    pub fn in_script(&self, character: &str, script: Script) -> bool {
+        let exclusive_to = |map: &HashMap<Ideograph, Vec<Ideograph>>| {
+            map.get(character)
+                .is_some_and(|forms| forms.iter().all(|f| f != character))
+        };
+        match script {
+            Script::Both => true,
+            Script::Simplified => !exclusive_to(&self.to_simplified),
+            Script::Traditional => !exclusive_to(&self.to_traditional),
+        }
+    }
+
+    /// Most common form of the character in the given script, if it differs
+    pub fn counterpart(&self, character: &str, script: Script) -> Option<&str> {
+        let map = match script {
+            Script::Both => return None,
+            Script::Simplified => &self.to_simplified,
+            Script::Traditional => &self.to_traditional,
+        };
+        map.get(character)
+            .and_then(|forms| forms.first())
+            .map(String::as_str)
+            .filter(|&f| f != character)
+    }
+
+    pub fn len(&self) -> usize {
+        self.to_traditional.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.to_traditional.is_empty()
+    }
+}
+
+/// Load a Simplified to Traditional mapping table (OpenCC STCharacters.txt format)
+/// Format: simplified\ttraditional [traditional ...] per line, most common form first
+pub fn load_variant_table<P: AsRef<Path>>(
+    path: P,
+) -> Result<ScriptTable, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    let reader = BufReader::new(file);
+    let mut table = ScriptTable::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (simplified, traditional) =
+            line.split_once('\t').ok_or("Missing traditional column")?;
+        for form in traditional.split_whitespace() {
+            table.insert(simplified.trim(), form);
+        }
+    }
+
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_variant_table() {
+        let path = std::env::temp_dir().join("juststrokes_variant_table_test.txt");
+        fs::write(&path, "内\t內\n干\t幹 乾 干\n乾\t乾 亁\n").unwrap();
+
+        let table = load_variant_table(&path).expect("Failed to load variant table");
+        assert_eq!(table.len(), 3);
+
+        assert!(table.in_script("内", Script::Simplified));
+        assert!(!table.in_script("內", Script::Simplified));
+        assert!(!table.in_script("内", Script::Traditional));
+        assert!(table.in_script("內", Script::Both));
+
+        // 干 maps to itself among others, 乾 is both a simplified and traditional form
+        assert!(table.in_script("干", Script::Traditional));
+        assert!(table.in_script("乾", Script::Simplified));
+        assert!(!table.in_script("幹", Script::Simplified));
+
+        assert_eq!(table.counterpart("內", Script::Simplified), Some("内"));
+        assert_eq!(table.counterpart("干", Script::Traditional), Some("幹"));
+        assert_eq!(table.counterpart("水", Script::Traditional), None);
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{Matcher, Stroke};
+use crate::charset::CharacterFilter;
+use crate::{Canvas, MatchQuery, Matcher, Stroke};
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
@@ -59,54 +60,206 @@ impl SocketService {
             reader.read_line(&mut line)?;
         }
 
-        // Parse CSV input: max_width\tmax_height\tstroke1_points\tstroke2_points\t...
-        // Each stroke: x0,y0,x1,y1,...
-        let parts: Vec<&str> = line.trim().split('\t').collect();
-        if parts.len() < 3 {
-            stream.write_all(b"ERROR\tInvalid input format\n")?;
-            return Ok(());
-        }
-
-        let _max_width: f64 = parts[0].parse()?;
-        let _max_height: f64 = parts[1].parse()?;
-
-        // Parse strokes
-        let mut strokes: Vec<Stroke> = Vec::new();
-        for stroke_str in &parts[2..] {
-            let coords: Vec<f64> = stroke_str
-                .split(',')
-                .filter_map(|s| s.parse().ok())
-                .collect();
-
-            if !coords.len().is_multiple_of(2) {
-                stream.write_all(b"ERROR\tInvalid stroke coordinates\n")?;
+        let request = match parse_request(&line) {
+            Ok(request) => request,
+            Err(message) => {
+                stream.write_all(format!("ERROR\t{}\n", message).as_bytes())?;
                 return Ok(());
             }
+        };
 
-            let mut stroke: Stroke = Vec::new();
-            for i in (0..coords.len()).step_by(2) {
-                stroke.push([coords[i], coords[i + 1]]);
+        // Requests select a registered charset by name and/or list allowed
+        // characters inline; with both, a character must be in each
+        let inline_filter = request.allow.as_deref().map(CharacterFilter::from_chars);
+        let named_filter = match &request.charset {
+            Some(name) => match self.matcher.charset(name) {
+                Some(filter) => Some(filter),
+                None => {
+                    stream.write_all(format!("ERROR\tUnknown charset {}\n", name).as_bytes())?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        let combined;
+        let filter = match (named_filter, &inline_filter) {
+            (Some(named), Some(inline)) => {
+                combined = named.intersection(inline);
+                Some(&combined)
             }
-            strokes.push(stroke);
-        }
+            (named, inline) => named.or(inline.as_ref()),
+        };
 
-        // Match strokes
-        let candidates = self.matcher.match_strokes(&strokes, 10);
+        let query = MatchQuery {
+            context: request.context.as_deref(),
+            filter,
+            canvas: Some(request.canvas),
+        };
+        let candidates = self.matcher.match_query(&request.strokes, 10, &query);
 
-        // Return results as CSV: char1\tscore1\tchar2\tscore2\t...
-        // Note: We don't have scores in current API, so just return characters
-        for (i, candidate) in candidates.iter().enumerate() {
-            if i > 0 {
-                stream.write_all(b"\t")?;
+        match request.mode {
+            Mode::Match => {
+                // Return results as CSV: char1\tchar2\t...
+                for (i, candidate) in candidates.iter().enumerate() {
+                    if i > 0 {
+                        stream.write_all(b"\t")?;
+                    }
+                    stream.write_all(candidate.character.as_bytes())?;
+                }
+                stream.write_all(b"\n")?;
+            }
+            Mode::Info => {
+                // One line per candidate: char\tpinyin\tradical\tdecomposition\tdefinition
+                for candidate in candidates {
+                    let metadata = self
+                        .matcher
+                        .metadata()
+                        .get(&candidate.character)
+                        .cloned()
+                        .unwrap_or_default();
+                    let fields = [
+                        candidate.character,
+                        metadata.pinyin.join(","),
+                        metadata.radical.unwrap_or_default(),
+                        metadata.decomposition.unwrap_or_default(),
+                        metadata.definition.unwrap_or_default(),
+                    ];
+                    let fields: Vec<String> = fields.iter().map(|f| sanitize_field(f)).collect();
+                    stream.write_all(fields.join("\t").as_bytes())?;
+                    stream.write_all(b"\n")?;
+                }
+            }
+            Mode::Debug => {
+                // One line per candidate:
+                // char\tscore\tgeometric\tadaptation\tfrequency\tcontext\tscript\treversed
+                // where reversed lists the strokes matched end to start (- for none)
+                for candidate in candidates {
+                    let reversed = if candidate.reversed.is_empty() {
+                        "-".to_string()
+                    } else {
+                        candidate
+                            .reversed
+                            .iter()
+                            .map(|i| i.to_string())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    };
+                    let line = format!(
+                        "{}\t{:.2}\t{:.2}\t{:.2}\t{:.2}\t{:.2}\t{:.2}\t{}\n",
+                        candidate.character,
+                        candidate.score,
+                        candidate.geometric,
+                        candidate.adaptation,
+                        candidate.frequency,
+                        candidate.context,
+                        candidate.script,
+                        reversed
+                    );
+                    stream.write_all(line.as_bytes())?;
+                }
             }
-            stream.write_all(candidate.as_bytes())?;
         }
-        stream.write_all(b"\n")?;
 
         Ok(())
     }
 }
 
+/// Response format requested by the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Candidate characters on a single line
+    Match,
+    /// One line per candidate with dictionary metadata
+    Info,
+    /// One line per candidate with the components of its score
+    Debug,
+}
+
+/// Parsed client request
+#[derive(Debug)]
+struct Request {
+    mode: Mode,
+    /// Previously committed text for language-model reranking
+    context: Option<String>,
+    /// Name of a registered character set to restrict candidates to
+    charset: Option<String>,
+    /// Inline list of allowed characters
+    allow: Option<String>,
+    /// Drawing area size, used by canvas-based normalization
+    canvas: Canvas,
+    strokes: Vec<Stroke>,
+}
+
+/// Parse a request line
+/// Format: [key=value\t...]max_width\tmax_height\tstroke1_points\tstroke2_points\t...
+/// Each stroke: x0,y0,x1,y1,...
+/// Supported options: mode=match|info|debug, context=<preceding text>,
+/// charset=<registered name>, allow=<allowed characters>
+fn parse_request(line: &str) -> Result<Request, String> {
+    let mut parts: Vec<&str> = line.trim().split('\t').collect();
+
+    // Leading key=value fields select request options
+    let option_count = parts.iter().take_while(|p| p.contains('=')).count();
+    let mut mode = Mode::Match;
+    let mut context = None;
+    let mut charset = None;
+    let mut allow = None;
+    for option in parts.drain(..option_count) {
+        let (key, value) = option.split_once('=').unwrap_or_default();
+        match (key, value) {
+            ("mode", "match") => mode = Mode::Match,
+            ("mode", "info") => mode = Mode::Info,
+            ("mode", "debug") => mode = Mode::Debug,
+            ("context", text) => context = Some(text.to_string()),
+            ("charset", name) => charset = Some(name.to_string()),
+            ("allow", characters) => allow = Some(characters.to_string()),
+            _ => return Err(format!("Unknown option {}", option)),
+        }
+    }
+
+    if parts.len() < 3 {
+        return Err("Invalid input format".to_string());
+    }
+
+    let canvas = Canvas {
+        width: parts[0].parse().map_err(|_| "Invalid canvas size")?,
+        height: parts[1].parse().map_err(|_| "Invalid canvas size")?,
+    };
+
+    // Parse strokes
+    let mut strokes: Vec<Stroke> = Vec::new();
+    for stroke_str in &parts[2..] {
+        let coords: Vec<f64> = stroke_str
+            .split(',')
+            .filter_map(|s| s.parse().ok())
+            .collect();
+
+        if !coords.len().is_multiple_of(2) {
+            return Err("Invalid stroke coordinates".to_string());
+        }
+
+        let mut stroke: Stroke = Vec::new();
+        for i in (0..coords.len()).step_by(2) {
+            stroke.push([coords[i], coords[i + 1]]);
+        }
+        strokes.push(stroke);
+    }
+
+    Ok(Request {
+        mode,
+        context,
+        charset,
+        allow,
+        canvas,
+        strokes,
+    })
+}
+
+/// Keep free-text fields from breaking the tab/newline framing
+fn sanitize_field(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
 /// Get default socket path based on user ID
 pub fn default_socket_path() -> String {
     let uid = unsafe { libc::getuid() };
@@ -126,7 +279,8 @@ mod tests {
         // Load database from JSON (more reliable)
         let data =
             crate::data::load_graphics_json("graphics.json").expect("Failed to load database");
-        let matcher = Matcher::new(data, None);
+        let mut matcher = Matcher::new(data, None);
+        matcher.add_charset("numbers", CharacterFilter::from_chars("一二三十"));
 
         // Use test socket path
         let socket_path = "/tmp/juststrokes_test.socket".to_string();
@@ -160,7 +314,48 @@ mod tests {
         assert!(!response.is_empty());
         assert!(!response.starts_with("ERROR"));
 
+        // A named charset and an inline list both apply
+        let mut stream = UnixStream::connect(&socket_path).expect("Failed to connect");
+        let request = "charset=numbers\tallow=一丨\t400\t400\t0,200,400,200\n";
+        stream
+            .write_all(request.as_bytes())
+            .expect("Failed to write");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .expect("Failed to read");
+        assert_eq!(response, "一\n");
+
         // Cleanup
         let _ = fs::remove_file(&socket_path_clone);
     }
+
+    #[test]
+    fn test_parse_request_options() {
+        let request = parse_request("mode=info\t400\t400\t0,0,100,100\n").unwrap();
+        assert_eq!(request.mode, Mode::Info);
+        assert_eq!(
+            request.canvas,
+            Canvas {
+                width: 400.0,
+                height: 400.0
+            }
+        );
+        assert_eq!(request.strokes, vec![vec![[0.0, 0.0], [100.0, 100.0]]]);
+
+        let request = parse_request("400\t400\t0,0,100,100\n").unwrap();
+        assert_eq!(request.mode, Mode::Match);
+        assert_eq!(request.context, None);
+
+        let request = parse_request("context=我们\tmode=debug\t400\t400\t0,0\n").unwrap();
+        assert_eq!(request.mode, Mode::Debug);
+        assert_eq!(request.context.as_deref(), Some("我们"));
+
+        let request = parse_request("charset=hsk1\tallow=一二\t400\t400\t0,0\n").unwrap();
+        assert_eq!(request.charset.as_deref(), Some("hsk1"));
+        assert_eq!(request.allow.as_deref(), Some("一二"));
+
+        assert!(parse_request("mode=fancy\t400\t400\t0,0\n").is_err());
+        assert!(parse_request("400\t400\t0,0,100\n").is_err());
+    }
 }
@@ -0,0 +1,464 @@
+use crate::stroke_type::{self, BasicStroke};
+use crate::{Ideograph, Matcher, NUM_POSSIBLE_ENCODED_VALUE, StrokeProcessed, encoded_points};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// One position of a stroke code query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Code(BasicStroke),
+    /// Exactly one stroke of any class ('?' or the input method's '6' key)
+    Any,
+    /// Any number of strokes ('*')
+    Many,
+}
+
+/// Stroke code sequence as typed on a 五笔画 keypad, e.g. "25*1" or "2?1"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StrokeQuery {
+    tokens: Vec<Token>,
+    /// Match characters whose sequence starts with the query
+    pub prefix: bool,
+}
+
+impl StrokeQuery {
+    /// Parse digits 1-5, '?' or '6' for one stroke of any class and '*' for
+    /// any number of strokes; whitespace is ignored
+    pub fn parse(text: &str, prefix: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut tokens = Vec::new();
+        for c in text.chars().filter(|c| !c.is_whitespace()) {
+            let token = match c {
+                '?' | '6' => Token::Any,
+                '*' => Token::Many,
+                _ => c
+                    .to_digit(10)
+                    .and_then(|d| BasicStroke::from_code(d as u8))
+                    .map(Token::Code)
+                    .ok_or_else(|| format!("Invalid stroke code '{}' in {}", c, text))?,
+            };
+            tokens.push(token);
+        }
+        if tokens.is_empty() {
+            return Err("Empty stroke code query".into());
+        }
+        if prefix {
+            tokens.push(Token::Many);
+        }
+        Ok(Self { tokens, prefix })
+    }
+
+    /// Number of strokes the query names explicitly
+    fn fixed_len(&self) -> usize {
+        self.tokens.iter().filter(|t| **t != Token::Many).count()
+    }
+
+    /// Fewest classes that must be wrong for `strokes` to match, or None if
+    /// the stroke count cannot fit the query
+    fn mismatches(&self, strokes: &[BasicStroke]) -> Option<usize> {
+        // Edit distance with substitutions only; '*' absorbs any run of strokes
+        let n = strokes.len();
+        let unreachable = usize::MAX;
+        let mut row = vec![unreachable; n + 1];
+        row[0] = 0;
+        for token in &self.tokens {
+            let mut next = vec![unreachable; n + 1];
+            for j in 0..=n {
+                next[j] = match token {
+                    Token::Many => {
+                        let skip = if j > 0 { next[j - 1] } else { unreachable };
+                        row[j].min(skip)
+                    }
+                    Token::Any if j > 0 => row[j - 1],
+                    Token::Code(code) if j > 0 && row[j - 1] != unreachable => {
+                        row[j - 1] + usize::from(strokes[j - 1] != *code)
+                    }
+                    _ => unreachable,
+                };
+            }
+            row = next;
+        }
+        (row[n] != unreachable).then_some(row[n])
+    }
+}
+
+/// Ranking of search results; all put results with fewer mismatching
+/// strokes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SearchOrder {
+    /// Most frequent characters first (see `Matcher::set_frequency_table`)
+    #[default]
+    Frequency,
+    /// Fewest strokes beyond those typed first, then by frequency. This only
+    /// counts strokes; no geometric score is involved
+    FewestStrokes,
+    /// Characters whose strokes run closest to the typical direction of their
+    /// class first (see `StrokeMatch::deviation`), then by frequency. Their
+    /// strokes are the least likely to be read as another class
+    Plausibility,
+}
+
+/// Search options for `StrokeCodeIndex::search`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchOptions {
+    pub order: SearchOrder,
+    /// Strokes whose class may differ from the query, to tolerate strokes
+    /// that classify as a neighbouring class
+    pub max_mismatches: usize,
+    pub limit: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            order: SearchOrder::Frequency,
+            max_mismatches: 0,
+            limit: 20,
+        }
+    }
+}
+
+/// Character found by a stroke code query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokeMatch {
+    pub character: Ideograph,
+    /// Stroke codes of the matching stroke order
+    pub codes: String,
+    /// Strokes whose class differs from the query
+    pub mismatches: usize,
+    /// Strokes matched by '*' or the prefix
+    pub extra_strokes: usize,
+    /// Corpus count from the frequency table (0 if unknown)
+    pub frequency: f64,
+    /// Mean squared angle between each stroke and the mean direction of its
+    /// class over the indexed templates, in units of the class's spread
+    /// (0 = every stroke runs in the typical direction)
+    pub deviation: f64,
+}
+
+/// Stroke code sequences of every template in a matcher's database
+pub struct StrokeCodeIndex<'a> {
+    matcher: &'a Matcher,
+    /// Character, stroke classes and `StrokeMatch::deviation` of each template
+    entries: Vec<(&'a str, Vec<BasicStroke>, f64)>,
+}
+
+impl<'a> StrokeCodeIndex<'a> {
+    /// Classify the strokes of every template the matcher selects, including
+    /// stroke order variants of the chosen standard (`MatcherOptions::standard`)
+    pub fn new(matcher: &'a Matcher) -> Self {
+        let entries = matcher
+            .medians
+            .iter()
+            .zip(&matcher.selected)
+            .filter(|(_, selected)| **selected)
+            .map(|(t, _)| {
+                let codes: Vec<_> = t
+                    .strokes
+                    .iter()
+                    .map(|s| stroke_type::classify_stroke(s).basic())
+                    .collect();
+                (t.character.as_str(), codes, &t.strokes)
+            })
+            .collect::<Vec<_>>();
+        let classes = ClassDirections::new(
+            entries
+                .iter()
+                .flat_map(|(_, codes, strokes)| codes.iter().copied().zip(strokes.iter())),
+        );
+        let entries = entries
+            .into_iter()
+            .map(|(character, codes, strokes)| {
+                let deviation = classes.deviation(&codes, strokes);
+                (character, codes, deviation)
+            })
+            .collect();
+        Self { matcher, entries }
+    }
+
+    /// Stroke codes of a character's first selected template, e.g. "251" for 口
+    pub fn codes(&self, character: &str) -> Option<String> {
+        self.entries
+            .iter()
+            .find(|(c, _, _)| *c == character)
+            .map(|(_, codes, _)| code_string(codes))
+    }
+
+    /// Characters whose stroke codes match the query, best first; each
+    /// character is reported once, for its best-matching stroke order
+    pub fn search(&self, query: &StrokeQuery, options: &SearchOptions) -> Vec<StrokeMatch> {
+        let fixed = query.fixed_len();
+        let mut best: HashMap<&str, StrokeMatch> = HashMap::new();
+        for (character, codes, deviation) in &self.entries {
+            let Some(mismatches) = query.mismatches(codes) else {
+                continue;
+            };
+            if mismatches > options.max_mismatches {
+                continue;
+            }
+            let found = StrokeMatch {
+                character: character.to_string(),
+                codes: code_string(codes),
+                mismatches,
+                extra_strokes: codes.len() - fixed,
+                frequency: self.matcher.frequency.count(character),
+                deviation: *deviation,
+            };
+            match best.get(character) {
+                Some(known) if compare(known, &found, options.order) != Ordering::Greater => {}
+                _ => {
+                    best.insert(character, found);
+                }
+            }
+        }
+
+        let mut results: Vec<StrokeMatch> = best.into_values().collect();
+        results.sort_by(|a, b| compare(a, b, options.order));
+        results.truncate(options.limit);
+        results
+    }
+}
+
+/// Direction of a preprocessed stroke in radians, from its encoded angle
+fn heading(stroke: &StrokeProcessed) -> f64 {
+    let angle = stroke[2 * encoded_points(stroke)];
+    angle * 2.0 * std::f64::consts::PI / NUM_POSSIBLE_ENCODED_VALUE as f64
+}
+
+/// Signed difference between two directions in radians, in [-π, π]
+fn angle_between(a: f64, b: f64) -> f64 {
+    (b - a).sin().atan2((b - a).cos())
+}
+
+/// Mean direction and root mean square spread of the strokes of each class;
+/// the spread is at least one step of the encoded angle
+struct ClassDirections {
+    mean: [f64; 6],
+    spread: [f64; 6],
+}
+
+impl ClassDirections {
+    fn new<'s>(strokes: impl Iterator<Item = (BasicStroke, &'s StrokeProcessed)> + Clone) -> Self {
+        let mut sum = [[0.0; 2]; 6];
+        for (class, stroke) in strokes.clone() {
+            let angle = heading(stroke);
+            sum[class.code() as usize][0] += angle.cos();
+            sum[class.code() as usize][1] += angle.sin();
+        }
+        let mean = sum.map(|[cos, sin]| sin.atan2(cos));
+
+        let (mut squares, mut count) = ([0.0; 6], [0usize; 6]);
+        for (class, stroke) in strokes {
+            let class = class.code() as usize;
+            squares[class] += angle_between(mean[class], heading(stroke)).powi(2);
+            count[class] += 1;
+        }
+        let step = 2.0 * std::f64::consts::PI / NUM_POSSIBLE_ENCODED_VALUE as f64;
+        let spread =
+            std::array::from_fn(|i| (squares[i] / count[i].max(1) as f64).sqrt().max(step));
+        Self { mean, spread }
+    }
+
+    /// `StrokeMatch::deviation` of a template
+    fn deviation(&self, codes: &[BasicStroke], strokes: &[StrokeProcessed]) -> f64 {
+        if strokes.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = codes
+            .iter()
+            .zip(strokes)
+            .map(|(class, stroke)| {
+                let class = class.code() as usize;
+                (angle_between(self.mean[class], heading(stroke)) / self.spread[class]).powi(2)
+            })
+            .sum();
+        total / strokes.len() as f64
+    }
+}
+
+fn code_string(codes: &[BasicStroke]) -> String {
+    codes.iter().map(|c| char::from(b'0' + c.code())).collect()
+}
+
+/// Ordering of two results, better first; ties are broken by character
+fn compare(a: &StrokeMatch, b: &StrokeMatch, order: SearchOrder) -> Ordering {
+    let by_frequency = b.frequency.total_cmp(&a.frequency);
+    a.mismatches
+        .cmp(&b.mismatches)
+        .then(match order {
+            SearchOrder::Frequency => by_frequency,
+            SearchOrder::FewestStrokes => a.extra_strokes.cmp(&b.extra_strokes).then(by_frequency),
+            SearchOrder::Plausibility => a.deviation.total_cmp(&b.deviation).then(by_frequency),
+        })
+        .then_with(|| a.character.cmp(&b.character))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frequency::FrequencyTable;
+    use crate::processed_stroke;
+
+    fn code(query: &str) -> Vec<BasicStroke> {
+        query
+            .bytes()
+            .map(|b| BasicStroke::from_code(b - b'0').unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_query_matching() {
+        let kou = code("251");
+        assert_eq!(
+            StrokeQuery::parse("251", false).unwrap().mismatches(&kou),
+            Some(0)
+        );
+        assert_eq!(
+            StrokeQuery::parse("2?1", false).unwrap().mismatches(&kou),
+            Some(0)
+        );
+        assert_eq!(
+            StrokeQuery::parse("2*", false).unwrap().mismatches(&kou),
+            Some(0)
+        );
+        assert_eq!(
+            StrokeQuery::parse("25", true).unwrap().mismatches(&kou),
+            Some(0)
+        );
+        assert_eq!(
+            StrokeQuery::parse("25", false).unwrap().mismatches(&kou),
+            None
+        );
+        assert_eq!(
+            StrokeQuery::parse("231", false).unwrap().mismatches(&kou),
+            Some(1)
+        );
+        assert_eq!(
+            StrokeQuery::parse("1*5*1", false).unwrap().mismatches(&kou),
+            Some(1)
+        );
+        assert!(StrokeQuery::parse("27", false).is_err());
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let heng = processed_stroke([0.0, 128.0], [255.0, 128.0]);
+        let shu = processed_stroke([128.0, 0.0], [128.0, 255.0]);
+        let tilted = processed_stroke([0.0, 100.0], [255.0, 160.0]);
+        let data = vec![
+            ("十".to_string(), vec![heng.clone(), shu.clone()]),
+            ("丅".to_string(), vec![tilted, shu.clone()]),
+            (
+                "土".to_string(),
+                vec![heng.clone(), shu.clone(), heng.clone()],
+            ),
+            ("丨".to_string(), vec![shu.clone()]),
+        ];
+        let mut matcher = Matcher::new(data, None);
+        matcher.set_frequency_table(FrequencyTable::from_counts([
+            ("十".to_string(), 10.0),
+            ("土".to_string(), 50.0),
+        ]));
+        let index = StrokeCodeIndex::new(&matcher);
+        assert_eq!(index.codes("土").as_deref(), Some("121"));
+
+        let characters = |query: &str, options: SearchOptions| -> Vec<String> {
+            let query = StrokeQuery::parse(query, true).unwrap();
+            index
+                .search(&query, &options)
+                .into_iter()
+                .map(|m| m.character)
+                .collect()
+        };
+        assert_eq!(
+            characters("12", SearchOptions::default()),
+            ["土", "十", "丅"]
+        );
+        let fewest = SearchOptions {
+            order: SearchOrder::FewestStrokes,
+            ..Default::default()
+        };
+        assert_eq!(characters("12", fewest), ["十", "丅", "土"]);
+        // The tilted heng of 丅 is the least typical of its class
+        let plausible = SearchOptions {
+            order: SearchOrder::Plausibility,
+            ..Default::default()
+        };
+        assert_eq!(characters("12", plausible), ["十", "土", "丅"]);
+
+        // A misclassified first stroke is tolerated on request
+        assert_eq!(
+            characters("22", SearchOptions::default()),
+            Vec::<String>::new()
+        );
+        let tolerant = SearchOptions {
+            max_mismatches: 1,
+            ..Default::default()
+        };
+        assert_eq!(characters("22", tolerant)[0], "土");
+    }
+
+    #[test]
+    fn test_search_selected_standard() {
+        let heng = processed_stroke([0.0, 128.0], [255.0, 128.0]);
+        let shu = processed_stroke([128.0, 0.0], [128.0, 255.0]);
+        let japanese = crate::variants::Template {
+            character: "十".to_string(),
+            standard: Some(crate::variants::Standard::Japan),
+            strokes: vec![shu.clone(), heng.clone()],
+        };
+        let found = |standard| -> Vec<String> {
+            let options = crate::MatcherOptions {
+                standard: Some(standard),
+                ..Default::default()
+            };
+            let mut matcher = Matcher::new(
+                vec![("十".to_string(), vec![heng.clone(), shu.clone()])],
+                Some(options),
+            );
+            matcher.add_variants(vec![japanese.clone()]).unwrap();
+            let index = StrokeCodeIndex::new(&matcher);
+            ["12", "21"]
+                .into_iter()
+                .filter(|codes| {
+                    let query = StrokeQuery::parse(codes, false).unwrap();
+                    !index.search(&query, &SearchOptions::default()).is_empty()
+                })
+                .map(String::from)
+                .collect()
+        };
+        assert_eq!(found(crate::variants::Standard::Japan), ["21"]);
+        assert_eq!(found(crate::variants::Standard::Prc), ["12"]);
+    }
+
+    #[test]
+    fn test_search_real_database() {
+        let data = crate::data::load_graphics_json("graphics.json").unwrap();
+        let matcher = Matcher::new(data, None);
+        let index = StrokeCodeIndex::new(&matcher);
+        let options = SearchOptions {
+            limit: 100,
+            ..Default::default()
+        };
+        for (codes, character) in [
+            ("3", "丿"),
+            ("13", "厂"),
+            ("12", "十"),
+            ("34", "八"),
+            ("35", "儿"),
+            ("251", "口"),
+            ("322", "川"),
+            ("2534", "水"),
+            ("3511", "月"),
+        ] {
+            let query = StrokeQuery::parse(codes, false).unwrap();
+            let found = index.search(&query, &options);
+            assert!(
+                found.iter().any(|m| m.character == character),
+                "{} not found for {}",
+                character,
+                codes
+            );
+        }
+    }
+}
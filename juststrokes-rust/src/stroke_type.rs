@@ -0,0 +1,472 @@
+use crate::{NUM_POSSIBLE_ENCODED_VALUE, StrokeProcessed, encoded_points};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Directions are screen degrees in (-180, 180]: 0 = right, 90 = down. The
+// straight strokes run in the canonical directions 45° apart: 横 0, 捺 45,
+// 竖 90, 撇 135 and 提 -45. A stroke belongs to the nearest one, so each
+// band reaches `BAND` either side of its direction.
+
+/// Half the angle between neighbouring canonical directions
+const BAND: f64 = 22.5;
+
+/// Leeway of the legs of a bent stroke. Sampled segments span equal arc
+/// lengths, so a leg's segment may take in up to a third of the next leg;
+/// across a right angle that tilts it by atan(1/2) ≈ 27°
+const LEG: f64 = 30.0;
+
+/// Smallest turn between the first and last segment of a bent stroke: a
+/// right angle, less the leeway of one leg
+const ZHE_TURN: f64 = 90.0 - LEG;
+
+/// Smallest turn between neighbouring segments that makes a corner. A right
+/// angle inside a segment is split between the turns on either side of it,
+/// about 45° each; the tip of a long 撇 curls by nearly as much, hence the
+/// leeway
+const CORNER_TURN: f64 = 50.0;
+
+/// Smallest turn into the end segment of a 竖钩 whose hook is too short to
+/// turn by `ZHE_TURN` overall. The hook flicks up to the left, 135° from
+/// the 竖, but shares its segment with the end of the 竖, which turns by
+/// about a third of that; less some leeway
+const HOOK_TURN: f64 = 40.0;
+
+/// A right angle a quarter of the way into a segment shortens its chord to
+/// √(1/16 + 9/16) ≈ 0.79 of its arc, so a chord this much shorter than the
+/// longest one has a corner inside it
+const CORNER_CHORD_RATIO: f64 = 0.8;
+
+/// Strokes with a shorter encoded length, a quarter of the diagonal (which
+/// encodes as 255), are dots unless they clearly run like a 横, 提 or 撇.
+/// Their few sampled points blur direction and bends
+const DIAN_LENGTH: f64 = 64.0;
+
+/// Straight strokes heading further right than this are 捺
+const SHU_FROM: f64 = 90.0 - BAND;
+
+/// A stroke whose last segment heads further left than this sweeps off like
+/// a 撇 or hooks like a 竖钩
+const PIE_END: f64 = 90.0 + BAND;
+
+/// Short strokes heading further right than this are 竖 rather than dots.
+/// The brush enters a short 竖 from the upper left, which leans its chord
+/// to the right by up to a quarter band past the 竖 band
+const SHORT_SHU_FROM: f64 = SHU_FROM - BAND / 4.0;
+
+/// Short strokes heading further left than this are dots falling to the
+/// left, as in 忄 and 宀, rather than short 竖, which lean left by a few
+/// degrees at most: a quarter band
+const DIAN_FROM: f64 = 90.0 + BAND / 4.0;
+
+/// Short strokes heading further left than this are 撇; left-falling dots
+/// stay a quarter band inside the 竖 band
+const SHORT_PIE_FROM: f64 = PIE_END - BAND / 4.0;
+
+/// The five basic stroke classes of the 五笔画 input method, by their key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BasicStroke {
+    /// 横 (also 提)
+    Heng = 1,
+    /// 竖
+    Shu = 2,
+    /// 撇
+    Pie = 3,
+    /// 点 (also 捺)
+    Dian = 4,
+    /// 折 (bent and hooked strokes, except 竖钩)
+    Zhe = 5,
+}
+
+impl BasicStroke {
+    /// Input method key 1-5
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(BasicStroke::Heng),
+            2 => Some(BasicStroke::Shu),
+            3 => Some(BasicStroke::Pie),
+            4 => Some(BasicStroke::Dian),
+            5 => Some(BasicStroke::Zhe),
+            _ => None,
+        }
+    }
+}
+
+/// Stroke type, including the common compound strokes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StrokeType {
+    /// ㇐ left to right
+    Heng,
+    /// ㇀ rising to the upper right
+    Ti,
+    /// ㇑ top to bottom
+    Shu,
+    /// ㇒ falling to the lower left
+    Pie,
+    /// ㇔ short stroke
+    Dian,
+    /// ㇏ long stroke falling to the lower right
+    Na,
+    /// ㇕ right, then down
+    HengZhe,
+    /// ㇇ right, then down to the left
+    HengPie,
+    /// ㇗ down, then right
+    ShuZhe,
+    /// ㇚ down, then a hook to the left
+    ShuGou,
+    /// Any other bent stroke
+    Zhe,
+}
+
+impl StrokeType {
+    /// Basic class of the stroke
+    pub fn basic(self) -> BasicStroke {
+        match self {
+            StrokeType::Heng | StrokeType::Ti => BasicStroke::Heng,
+            StrokeType::Shu => BasicStroke::Shu,
+            StrokeType::Pie => BasicStroke::Pie,
+            StrokeType::Dian | StrokeType::Na => BasicStroke::Dian,
+            // 五笔画 counts 亅 as a 竖 (了 is 52, 小 is 234)
+            StrokeType::ShuGou => BasicStroke::Shu,
+            StrokeType::HengZhe | StrokeType::HengPie | StrokeType::ShuZhe | StrokeType::Zhe => {
+                BasicStroke::Zhe
+            }
+        }
+    }
+
+    /// Pinyin name of the stroke
+    pub fn name(self) -> &'static str {
+        match self {
+            StrokeType::Heng => "heng",
+            StrokeType::Ti => "ti",
+            StrokeType::Shu => "shu",
+            StrokeType::Pie => "pie",
+            StrokeType::Dian => "dian",
+            StrokeType::Na => "na",
+            StrokeType::HengZhe => "hengzhe",
+            StrokeType::HengPie => "hengpie",
+            StrokeType::ShuZhe => "shuzhe",
+            StrokeType::ShuGou => "shugou",
+            StrokeType::Zhe => "zhe",
+        }
+    }
+
+    /// CJK stroke symbol
+    pub fn symbol(self) -> char {
+        match self {
+            StrokeType::Heng => '㇐',
+            StrokeType::Ti => '㇀',
+            StrokeType::Shu => '㇑',
+            StrokeType::Pie => '㇒',
+            StrokeType::Dian => '㇔',
+            StrokeType::Na => '㇏',
+            StrokeType::HengZhe => '㇕',
+            StrokeType::HengPie => '㇇',
+            StrokeType::ShuZhe => '㇗',
+            StrokeType::ShuGou => '㇚',
+            StrokeType::Zhe => '㇄',
+        }
+    }
+}
+
+impl fmt::Display for StrokeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Screen direction in degrees, in (-180, 180]: 0 = right, 90 = down
+fn direction(dx: f64, dy: f64) -> f64 {
+    dy.atan2(dx).to_degrees()
+}
+
+/// Signed difference from direction `a` to direction `b`, in (-180, 180]
+fn turn(a: f64, b: f64) -> f64 {
+    let d = (b - a).rem_euclid(360.0);
+    if d > 180.0 { d - 360.0 } else { d }
+}
+
+fn in_range(angle: f64, from: f64, to: f64) -> bool {
+    angle > from && angle <= to
+}
+
+/// Whether `angle` lies within `leeway` of `direction`
+fn near(angle: f64, direction: f64, leeway: f64) -> bool {
+    turn(direction, angle).abs() < leeway
+}
+
+/// Whether the end of a downward stroke hooks back to the left, as in 亅:
+/// left of the 竖 band and less than 45° above the horizontal
+fn hooks(last: f64) -> bool {
+    in_range(last, PIE_END, 180.0) || in_range(last, -180.0, -135.0)
+}
+
+/// Classify a preprocessed stroke from its encoded angle and length and the
+/// directions of its sampled segments
+/// Strokes whose segments turn by `ZHE_TURN` at a corner are bent; others are
+/// classified by their overall direction, with the end segment telling a
+/// 撇 that starts vertically from a 竖
+pub fn classify_stroke(stroke: &StrokeProcessed) -> StrokeType {
+    let points = encoded_points(stroke);
+    let angle_idx = 2 * points;
+    let length = stroke[angle_idx + 1];
+    let heading = stroke[angle_idx] * 360.0 / NUM_POSSIBLE_ENCODED_VALUE as f64 - 180.0;
+
+    // Segment directions; a 2-point stroke is its own chord
+    let segments: Vec<(f64, f64)> = (0..points.saturating_sub(1))
+        .map(|i| {
+            let (dx, dy) = (
+                stroke[2 * i + 2] - stroke[2 * i],
+                stroke[2 * i + 3] - stroke[2 * i + 1],
+            );
+            (direction(dx, dy), dx.hypot(dy))
+        })
+        .collect();
+    let (first, last) = match (segments.first(), segments.last()) {
+        (Some(first), Some(last)) if segments.len() >= 2 => (first.0, last.0),
+        _ => (heading, heading),
+    };
+    // Largest turn between any two segments: a ㇈ turns down and back again,
+    // so its first and last segments can be parallel
+    let spread = segments
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| segments[i + 1..].iter().map(move |b| turn(a.0, b.0).abs()))
+        .fold(0.0, f64::max);
+    let sharpest = segments
+        .windows(2)
+        .map(|w| turn(w[0].0, w[1].0).abs())
+        .fold(0.0, f64::max);
+    let longest = segments.iter().map(|s| s.1).fold(0.0, f64::max);
+    let shortest = segments.iter().map(|s| s.1).fold(f64::MAX, f64::min);
+    let corner = sharpest >= CORNER_TURN || shortest < CORNER_CHORD_RATIO * longest;
+
+    // Bends of short strokes are blurred by the few sampled points
+    let bend = if length <= DIAN_LENGTH {
+        2.0 * ZHE_TURN - CORNER_TURN
+    } else {
+        ZHE_TURN
+    };
+    // Only the end of a 竖钩 leaves the vertical
+    let vertical = segments[..segments.len().saturating_sub(1)]
+        .iter()
+        .all(|s| near(s.0, 90.0, LEG));
+    // A hook is a short end segment turning away from the rest
+    let hook = match segments.len() {
+        0 | 1 => false,
+        n => {
+            turn(segments[n - 2].0, last).abs() >= HOOK_TURN
+                && segments[n - 1].1 < CORNER_CHORD_RATIO * longest
+        }
+    };
+
+    if spread >= bend && corner {
+        let heng = near(first, 0.0, LEG);
+        let shu = near(first, 90.0, LEG);
+        return if heng && near(last, 90.0, LEG) {
+            StrokeType::HengZhe
+        } else if heng && in_range(last, 90.0 + LEG, 180.0) {
+            StrokeType::HengPie
+        } else if shu && near(last, 0.0, LEG) {
+            StrokeType::ShuZhe
+        } else if shu && vertical && hooks(last) {
+            StrokeType::ShuGou
+        } else {
+            StrokeType::Zhe
+        };
+    }
+    // 撇点 (女): down, then off to the lower right, too round for a corner
+    if near(first, 90.0, BAND) && in_range(last, 0.0, SHU_FROM) && spread >= ZHE_TURN / 2.0 {
+        return StrokeType::Zhe;
+    }
+
+    if length <= DIAN_LENGTH {
+        return if in_range(heading, -BAND, BAND) {
+            StrokeType::Heng
+        } else if in_range(heading, -90.0, -BAND) {
+            StrokeType::Ti
+        } else if in_range(heading, SHORT_SHU_FROM, DIAN_FROM) && last <= PIE_END {
+            StrokeType::Shu
+        } else if in_range(heading, SHORT_PIE_FROM, 180.0) || (heading > 90.0 && last > PIE_END) {
+            StrokeType::Pie
+        } else {
+            StrokeType::Dian
+        };
+    }
+
+    // Straight strokes by their overall direction
+    if in_range(heading, -BAND, BAND) {
+        // 平捺 (辶): falls a little overall, dipping into the 捺 band on the way
+        if heading > 0.0 && segments.iter().any(|s| s.0 >= 2.0 * BAND / 3.0) {
+            StrokeType::Na
+        } else {
+            StrokeType::Heng
+        }
+    } else if in_range(heading, -90.0, -BAND) {
+        StrokeType::Ti
+    } else if in_range(heading, BAND, SHU_FROM) {
+        StrokeType::Na
+    } else if in_range(heading, SHU_FROM, PIE_END) && !(heading > 90.0 && last > PIE_END) {
+        StrokeType::Shu
+    } else if in_range(heading, SHU_FROM, PIE_END) && vertical && hook && hooks(last) {
+        // A 竖钩 whose hook turns less than `ZHE_TURN`
+        StrokeType::ShuGou
+    } else {
+        StrokeType::Pie
+    }
+}
+
+/// Stroke types of a character, in stroke order
+pub fn stroke_types(strokes: &[StrokeProcessed]) -> Vec<StrokeType> {
+    strokes.iter().map(classify_stroke).collect()
+}
+
+/// 五笔画 codes of a character, e.g. "251" for 口
+pub fn stroke_codes(strokes: &[StrokeProcessed]) -> String {
+    strokes
+        .iter()
+        .map(|s| char::from(b'0' + classify_stroke(s).basic().code()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptation::Distortion;
+    use crate::{MatcherOptions, Stroke, StrokeLayout, preprocess_strokes};
+    use std::fs;
+
+    fn classify(strokes: &[Stroke]) -> Vec<StrokeType> {
+        let processed = preprocess_strokes(
+            strokes,
+            StrokeLayout::default(),
+            &MatcherOptions::default(),
+            &Distortion::default(),
+            None,
+        );
+        stroke_types(&processed)
+    }
+
+    #[test]
+    fn test_classify_strokes() {
+        // 口: shu, hengzhe, heng
+        let kou = vec![
+            vec![[10.0, 10.0], [10.0, 90.0]],
+            vec![[10.0, 10.0], [90.0, 10.0], [90.0, 90.0]],
+            vec![[10.0, 90.0], [90.0, 90.0]],
+        ];
+        assert_eq!(
+            classify(&kou),
+            vec![StrokeType::Shu, StrokeType::HengZhe, StrokeType::Heng]
+        );
+
+        // 八 with a dot above: dian, pie, na
+        let strokes = vec![
+            vec![[48.0, 0.0], [54.0, 8.0]],
+            vec![[45.0, 20.0], [5.0, 100.0]],
+            vec![[55.0, 20.0], [95.0, 100.0]],
+        ];
+        let types = classify(&strokes);
+        assert_eq!(
+            types,
+            vec![StrokeType::Dian, StrokeType::Pie, StrokeType::Na]
+        );
+        assert_eq!(types[2].basic(), BasicStroke::Dian);
+
+        // ㇗ and a rising ti
+        let strokes = vec![
+            vec![[10.0, 10.0], [10.0, 90.0], [90.0, 90.0]],
+            vec![[20.0, 60.0], [80.0, 30.0]],
+        ];
+        assert_eq!(classify(&strokes), vec![StrokeType::ShuZhe, StrokeType::Ti]);
+    }
+
+    #[test]
+    fn test_codes_of_real_medians() {
+        let data = crate::data::load_graphics_json("graphics.json").unwrap();
+        let codes: std::collections::HashMap<_, _> = data
+            .iter()
+            .map(|(character, strokes)| (character.as_str(), stroke_codes(strokes)))
+            .collect();
+        // Long 撇, 平捺, ㇈, 撇点 and 竖钩 alongside the plain strokes
+        let expected = [
+            ("一", "1"),
+            ("丿", "3"),
+            ("十", "12"),
+            ("厂", "13"),
+            ("儿", "35"),
+            ("了", "52"),
+            ("八", "34"),
+            ("人", "34"),
+            ("口", "251"),
+            ("川", "322"),
+            ("女", "531"),
+            ("飞", "534"),
+            ("小", "234"),
+            ("火", "4334"),
+            ("月", "3511"),
+            ("水", "2534"),
+            ("手", "3112"),
+            ("中", "2512"),
+            ("大", "134"),
+            ("天", "1134"),
+            ("日", "2511"),
+            ("山", "252"),
+            ("石", "13251"),
+            ("四", "25351"),
+            ("牛", "3112"),
+            ("东", "15234"),
+            ("这", "4134454"),
+            ("的", "32511354"),
+            ("你", "3235234"),
+            ("我", "3121534"),
+            ("他", "32525"),
+            ("是", "251112134"),
+            ("打", "12112"),
+            ("时", "2511124"),
+        ];
+        let wrong: Vec<_> = expected
+            .iter()
+            .filter(|(character, code)| codes[character] != *code)
+            .map(|(character, code)| format!("{}: {} != {}", character, codes[character], code))
+            .collect();
+        assert!(wrong.is_empty(), "{:?}", wrong);
+    }
+
+    #[test]
+    fn test_class_accuracy_on_reference_codes() {
+        let data = crate::data::load_graphics_json("graphics.json").unwrap();
+        let codes: std::collections::HashMap<_, _> = data
+            .iter()
+            .map(|(character, strokes)| (character.as_str(), stroke_codes(strokes)))
+            .collect();
+        let reference = fs::read_to_string("tests/stroke_codes.txt").unwrap();
+
+        // Strokes per reference class, and how many of them classify alike
+        let (mut total, mut correct) = ([0usize; 6], [0usize; 6]);
+        for line in reference.lines().filter(|l| !l.starts_with('#')) {
+            let (character, expected) = line.split_once('\t').unwrap();
+            let found = &codes[character];
+            assert_eq!(found.len(), expected.len(), "{}", character);
+            for (want, got) in expected.bytes().zip(found.bytes()) {
+                let class = usize::from(want - b'0');
+                total[class] += 1;
+                correct[class] += usize::from(want == got);
+            }
+        }
+        for class in 1..=5 {
+            let accuracy = correct[class] as f64 / total[class] as f64;
+            assert!(
+                total[class] >= 500,
+                "{} strokes of class {}",
+                total[class],
+                class
+            );
+            assert!(accuracy >= 0.95, "class {}: {:.3}", class, accuracy);
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use crate::data::RawDatabase;
+use crate::{Point, Stroke};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Options for converting SVG path data into strokes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathOptions {
+    /// Line segments per Bézier curve; stroke datasets draw short curves, and
+    /// strokes are resampled to a few points afterwards
+    pub curve_segments: usize,
+    /// Accept closed subpaths (Z). Stroke datasets that draw each stroke as
+    /// its outline (makemeahanzi `strokes`, AnimCJK) close every subpath, and
+    /// an outline starts and ends at the same point, so its angle and length
+    /// say nothing about the stroke; only centerlines are useful
+    pub allow_closed: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        Self {
+            curve_segments: 8,
+            allow_closed: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Command(char),
+    Number(f64),
+}
+
+/// Split path data into commands and numbers; numbers may be run together
+/// as in "1.41-0.5.5" (1.41, -0.5, 0.5)
+fn tokenize(d: &str) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
+    let bytes = d.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() || c == b',' {
+            i += 1;
+        } else if c.is_ascii_alphabetic() && c != b'e' && c != b'E' {
+            tokens.push(Token::Command(c as char));
+            i += 1;
+        } else {
+            let start = i;
+            if bytes[i] == b'+' || bytes[i] == b'-' {
+                i += 1;
+            }
+            let mut dot = false;
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || (bytes[i] == b'.' && !dot)) {
+                dot |= bytes[i] == b'.';
+                i += 1;
+            }
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                i += 1;
+                if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+                    i += 1;
+                }
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let number = &d[start..i];
+            let value = number
+                .parse()
+                .map_err(|_| format!("Invalid number '{}' in path {}", number, d))?;
+            tokens.push(Token::Number(value));
+        }
+    }
+    Ok(tokens)
+}
+
+fn cubic(p0: Point, p1: Point, p2: Point, p3: Point, t: f64) -> Point {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    [
+        a * p0[0] + b * p1[0] + c * p2[0] + d * p3[0],
+        a * p0[1] + b * p1[1] + c * p2[1] + d * p3[1],
+    ]
+}
+
+fn quadratic(p0: Point, p1: Point, p2: Point, t: f64) -> Point {
+    let u = 1.0 - t;
+    let (a, b, c) = (u * u, 2.0 * u * t, t * t);
+    [
+        a * p0[0] + b * p1[0] + c * p2[0],
+        a * p0[1] + b * p1[1] + c * p2[1],
+    ]
+}
+
+fn reflect(control: Option<Point>, current: Point) -> Point {
+    control.map_or(current, |p| {
+        [2.0 * current[0] - p[0], 2.0 * current[1] - p[1]]
+    })
+}
+
+/// Flatten SVG path data into one polyline per subpath
+/// Supports M, L, H, V, C, S, Q, T and Z, absolute and relative; Bézier
+/// curves are split into `curve_segments` pieces of equal parameter length.
+/// Elliptical arcs (A) are rejected, since stroke datasets do not use them,
+/// and so is Z unless `allow_closed` is set.
+pub fn parse_path(
+    d: &str,
+    options: &PathOptions,
+) -> Result<Vec<Stroke>, Box<dyn std::error::Error>> {
+    let tokens = tokenize(d)?;
+    let segments = options.curve_segments.max(1);
+    let mut strokes = Vec::new();
+    let mut line: Stroke = Vec::new();
+    let (mut current, mut start): (Point, Point) = ([0.0, 0.0], [0.0, 0.0]);
+    // Last control points, reflected by S and T
+    let (mut cubic_control, mut quad_control): (Option<Point>, Option<Point>) = (None, None);
+    let mut command = None;
+    let mut started = false;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if let Token::Command(c) = tokens[i] {
+            i += 1;
+            if c.eq_ignore_ascii_case(&'Z') {
+                if !options.allow_closed {
+                    return Err(format!(
+                        "Closed subpath in {}: strokes must be centerlines, not outlines",
+                        d
+                    )
+                    .into());
+                }
+                if !line.is_empty() {
+                    line.push(start);
+                    strokes.push(std::mem::take(&mut line));
+                }
+                current = start;
+                (cubic_control, quad_control) = (None, None);
+                command = None;
+                continue;
+            }
+            command = Some(c);
+        }
+        let c = command.ok_or_else(|| format!("Missing path command in {}", d))?;
+        let upper = c.to_ascii_uppercase();
+        let arity = match upper {
+            'H' | 'V' => 1,
+            'M' | 'L' | 'T' => 2,
+            'S' | 'Q' => 4,
+            'C' => 6,
+            _ => return Err(format!("Unsupported path command '{}' in {}", c, d).into()),
+        };
+        let args: Vec<f64> = tokens
+            .get(i..i + arity)
+            .and_then(|t| {
+                t.iter()
+                    .map(|t| match t {
+                        Token::Number(v) => Some(*v),
+                        Token::Command(_) => None,
+                    })
+                    .collect()
+            })
+            .ok_or_else(|| format!("Missing arguments for '{}' in path {}", c, d))?;
+        i += arity;
+
+        let origin = if c.is_ascii_lowercase() {
+            current
+        } else {
+            [0.0, 0.0]
+        };
+        let point = |k: usize| [origin[0] + args[2 * k], origin[1] + args[2 * k + 1]];
+        if upper == 'M' {
+            if !line.is_empty() {
+                strokes.push(std::mem::take(&mut line));
+            }
+            current = point(0);
+            start = current;
+            started = true;
+            line.push(current);
+            (cubic_control, quad_control) = (None, None);
+            // Further coordinate pairs after a moveto are linetos
+            command = Some(if c == 'M' { 'L' } else { 'l' });
+            continue;
+        }
+        if !started {
+            return Err(format!("Path must start with a moveto: {}", d).into());
+        }
+        if line.is_empty() {
+            // Drawing after a closepath starts a new subpath at its start
+            line.push(current);
+        }
+
+        let (mut next_cubic, mut next_quad) = (None, None);
+        let end = match upper {
+            'L' => {
+                let p = point(0);
+                line.push(p);
+                p
+            }
+            'H' => {
+                let p = [origin[0] + args[0], current[1]];
+                line.push(p);
+                p
+            }
+            'V' => {
+                let p = [current[0], origin[1] + args[0]];
+                line.push(p);
+                p
+            }
+            'C' | 'S' => {
+                let (p1, p2, p3) = if upper == 'C' {
+                    (point(0), point(1), point(2))
+                } else {
+                    (reflect(cubic_control, current), point(0), point(1))
+                };
+                for step in 1..=segments {
+                    line.push(cubic(current, p1, p2, p3, step as f64 / segments as f64));
+                }
+                next_cubic = Some(p2);
+                p3
+            }
+            _ => {
+                let (p1, p2) = if upper == 'Q' {
+                    (point(0), point(1))
+                } else {
+                    (reflect(quad_control, current), point(0))
+                };
+                for step in 1..=segments {
+                    line.push(quadratic(current, p1, p2, step as f64 / segments as f64));
+                }
+                next_quad = Some(p1);
+                p2
+            }
+        };
+        current = end;
+        (cubic_control, quad_control) = (next_cubic, next_quad);
+    }
+
+    if !line.is_empty() {
+        strokes.push(line);
+    }
+    Ok(strokes)
+}
+
+/// Value of attribute `name` in the start tag `tag`
+pub(crate) fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().next_back();
+        let after = rest[pos + name.len()..].trim_start();
+        rest = &rest[pos + name.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        return value[1..].find(quote).map(|end| &value[1..1 + end]);
+    }
+    None
+}
+
+/// Path data (`d` attributes) of the `<path>` elements, in document order
+pub fn path_data(svg: &str) -> Result<Vec<&str>, Box<dyn std::error::Error>> {
+    let mut result = Vec::new();
+    let mut rest = svg;
+    while let Some(start) = rest.find("<path") {
+        rest = &rest[start..];
+        let end = rest.find('>').ok_or("Unterminated <path> element")?;
+        if let Some(d) = attribute(&rest[..end], "d") {
+            result.push(d);
+        }
+        rest = &rest[end..];
+    }
+    Ok(result)
+}
+
+/// Strokes of an SVG document: every subpath of every `<path>` element, in
+/// document order
+pub fn parse_svg(
+    svg: &str,
+    options: &PathOptions,
+) -> Result<Vec<Stroke>, Box<dyn std::error::Error>> {
+    let mut strokes = Vec::new();
+    for d in path_data(svg)? {
+        strokes.extend(parse_path(d, options)?);
+    }
+    Ok(strokes)
+}
+
+/// Character named by an SVG file stem: the character itself ("永"), its
+/// code point as KanjiVG writes it, in five hex digits ("06c38", "20b9f"),
+/// or with a `U+` prefix and four to six hex digits ("U+6C38"). Other names
+/// give None: variants such as "06c38-Kaisho", words such as "cafe", and
+/// AnimCJK's decimal names such as "20108"; five plain digits without a
+/// leading zero read as decimal, so such code points need the prefix
+pub fn character_of(stem: &str) -> Option<String> {
+    let mut chars = stem.chars();
+    if let (Some(c), None) = (chars.next(), chars.next())
+        && !c.is_ascii()
+    {
+        return Some(c.to_string());
+    }
+    let code = match stem.strip_prefix("U+").or_else(|| stem.strip_prefix("u+")) {
+        Some(code) if (4..=6).contains(&code.len()) => code,
+        Some(_) => return None,
+        None if stem.len() == 5
+            && (stem.starts_with('0') || stem.chars().any(|c| c.is_ascii_alphabetic())) =>
+        {
+            stem
+        }
+        None => return None,
+    };
+    if !code.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(code, 16).ok()?;
+    char::from_u32(value).map(|c| c.to_string())
+}
+
+/// Load one character per SVG file of a directory, named as in `character_of`
+/// Files with other names or without paths are skipped; coordinates keep the
+/// SVG orientation (y pointing down). Paths must be stroke centerlines; stroke
+/// outlines are closed and fail unless `allow_closed` is set.
+pub fn load_svg_dir<P: AsRef<Path>>(
+    dir: P,
+    options: &PathOptions,
+) -> Result<RawDatabase, Box<dyn std::error::Error>> {
+    let mut files: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.sort();
+
+    let mut result = Vec::new();
+    for file in files {
+        if file.extension().is_none_or(|e| e != "svg") {
+            continue;
+        }
+        let stem = file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        let Some(character) = character_of(stem) else {
+            continue;
+        };
+        let strokes = parse_svg(&fs::read_to_string(&file)?, options)
+            .map_err(|e| format!("{}: {}", file.display(), e))?;
+        if !strokes.is_empty() {
+            result.push((character, strokes));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path_commands() {
+        let options = PathOptions {
+            curve_segments: 2,
+            allow_closed: true,
+        };
+        let strokes = parse_path("M0 0H10V10h-5v5L0 20", &options).unwrap();
+        assert_eq!(
+            strokes,
+            vec![vec![
+                [0.0, 0.0],
+                [10.0, 0.0],
+                [10.0, 10.0],
+                [5.0, 10.0],
+                [5.0, 15.0],
+                [0.0, 20.0]
+            ]]
+        );
+
+        // Quadratic curve and its smooth continuation, reflected about (20, 0)
+        let strokes = parse_path("M0,0Q10,20,20,0t20,0", &options).unwrap();
+        assert_eq!(
+            strokes,
+            vec![vec![
+                [0.0, 0.0],
+                [10.0, 10.0],
+                [20.0, 0.0],
+                [30.0, -10.0],
+                [40.0, 0.0]
+            ]]
+        );
+
+        // Subpaths become separate strokes; Z returns to the subpath start
+        let strokes = parse_path("M0,0l10,0z m0,10 l0,10", &options).unwrap();
+        assert_eq!(
+            strokes,
+            vec![
+                vec![[0.0, 0.0], [10.0, 0.0], [0.0, 0.0]],
+                vec![[0.0, 10.0], [0.0, 20.0]],
+            ]
+        );
+
+        // Outlines are refused unless closed subpaths are allowed
+        let outline = "M0,0L10,0L10,2L0,2Z";
+        assert!(parse_path(outline, &PathOptions::default()).is_err());
+        assert_eq!(parse_path(outline, &options).unwrap()[0].len(), 5);
+
+        assert!(parse_path("M0,0 A5,5,0,0,1,10,10", &options).is_err());
+        assert!(parse_path("M0,0 L10", &options).is_err());
+        assert!(parse_path("10,10", &options).is_err());
+    }
+
+    #[test]
+    fn test_load_svg_dir() {
+        assert_eq!(character_of("永").as_deref(), Some("永"));
+        assert_eq!(character_of("06c38").as_deref(), Some("永"));
+        assert_eq!(character_of("U+6C38").as_deref(), Some("永"));
+        assert_eq!(character_of("06c38-Kaisho"), None);
+        assert_eq!(character_of("20b9f").as_deref(), Some("𠮟"));
+        assert_eq!(character_of("U+20108").as_deref(), Some("𠄈"));
+        for name in [
+            "cafe", "face", "20108", "6c38", "006c38", "0zzzz", "U+6C", "U++6C38",
+        ] {
+            assert_eq!(character_of(name), None, "{}", name);
+        }
+
+        let dir = std::env::temp_dir().join("juststrokes_svg_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let svg = r#"<svg viewBox="0 0 100 100">
+<path d="M10,50 C30,45 70,45 90,50"/>
+<path style="fill:none" d='M50,10 Q52,50 50,90'/>
+<text x="5" y="5">1</text>
+</svg>"#;
+        fs::write(dir.join("十.svg"), svg).unwrap();
+        fs::write(dir.join("notes.txt"), svg).unwrap();
+        fs::write(dir.join("05341.svg"), "<svg></svg>").unwrap();
+
+        let raw = load_svg_dir(&dir, &PathOptions::default()).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(raw.len(), 1);
+        assert_eq!(raw[0].0, "十");
+        assert_eq!(raw[0].1.len(), 2);
+        assert_eq!(raw[0].1[1].len(), 9);
+        assert_eq!(raw[0].1[1][8], [50.0, 90.0]);
+    }
+}
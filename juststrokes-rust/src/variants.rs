@@ -0,0 +1,155 @@
+use crate::{Ideograph, StrokeProcessed};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Regional writing standard a stroke order belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Standard {
+    /// Mainland China (GB)
+    Prc,
+    /// Taiwan (Ministry of Education)
+    Taiwan,
+    /// Hong Kong (HKSCS / EDB)
+    HongKong,
+    /// Japan (Jōyō kanji)
+    Japan,
+}
+
+impl FromStr for Standard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "prc" | "cn" | "zh-cn" => Ok(Standard::Prc),
+            "taiwan" | "tw" | "zh-tw" => Ok(Standard::Taiwan),
+            "hongkong" | "hk" | "zh-hk" => Ok(Standard::HongKong),
+            "japan" | "jp" | "ja" => Ok(Standard::Japan),
+            _ => Err(format!("Unknown writing standard: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Standard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Standard::Prc => "prc",
+            Standard::Taiwan => "tw",
+            Standard::HongKong => "hk",
+            Standard::Japan => "jp",
+        })
+    }
+}
+
+/// One accepted stroke order of a character
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub character: Ideograph,
+    /// Standard this stroke order comes from (None for the base database form)
+    pub standard: Option<Standard>,
+    pub strokes: Vec<StrokeProcessed>,
+}
+
+/// Load stroke-order variants from CSV file
+/// Format: character\tstandard\tx0,y0,x1,y1,x2,y2,x3,y3,angle,length\t...
+/// Standards: prc, tw, hk, jp
+pub fn load_variants_csv<P: AsRef<Path>>(
+    path: P,
+) -> Result<Vec<Template>, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    let reader = BufReader::new(file);
+    let mut result = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.split('\t');
+
+        let character = parts.next().ok_or("Missing character column")?.to_string();
+        let standard: Standard = parts.next().ok_or("Missing standard column")?.parse()?;
+
+        let mut strokes = Vec::new();
+        for stroke_str in parts {
+            let values: Result<Vec<f64>, _> =
+                stroke_str.split(',').map(|s| s.parse::<f64>()).collect();
+            strokes.push(values?);
+        }
+
+        result.push(Template {
+            character,
+            standard: Some(standard),
+            strokes,
+        });
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Matcher, processed_stroke};
+
+    #[test]
+    fn test_load_variants_csv() {
+        let path = std::env::temp_dir().join("juststrokes_variants_test.csv");
+        fs::write(
+            &path,
+            "火\ttw\t1,2,3,4,5,6,7,8,9,10\n火\tjp\t1,2,3,4,5,6,7,8,9,10\n",
+        )
+        .unwrap();
+
+        let variants = load_variants_csv(&path).expect("Failed to load variants");
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].character, "火");
+        assert_eq!(variants[0].standard, Some(Standard::Taiwan));
+        assert_eq!(variants[1].standard, Some(Standard::Japan));
+        assert_eq!(variants[1].strokes[0].len(), 10);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_variants_collapse_to_best() {
+        let horizontal = processed_stroke([0.0, 128.0], [255.0, 128.0]);
+        let tilted = processed_stroke([0.0, 120.0], [255.0, 136.0]);
+        let vertical = processed_stroke([128.0, 0.0], [128.0, 255.0]);
+
+        let mut matcher = Matcher::new(
+            vec![
+                ("一".to_string(), vec![tilted]),
+                ("丨".to_string(), vec![vertical]),
+            ],
+            None,
+        );
+        matcher
+            .add_variants(vec![Template {
+                character: "一".to_string(),
+                standard: Some(Standard::Taiwan),
+                strokes: vec![horizontal.clone()],
+            }])
+            .unwrap();
+
+        let candidates = matcher.match_preprocessed_scored(std::slice::from_ref(&horizontal), 2);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].character, "一");
+        assert_eq!(candidates[0].standard, Some(Standard::Taiwan));
+        assert_eq!(candidates[0].geometric, 0.0);
+        assert_eq!(candidates[1].character, "丨");
+
+        // A variant sampled with other points per stroke is refused whole
+        let eight_points = Template {
+            character: "丨".to_string(),
+            standard: None,
+            strokes: vec![vec![128.0; 18]],
+        };
+        assert!(matcher.add_variants(vec![eight_points]).is_err());
+        let candidates = matcher.match_preprocessed_scored(&[horizontal], 2);
+        assert_eq!(candidates.len(), 2);
+    }
+}